@@ -0,0 +1,2 @@
+ALTER TABLE projects_progress DROP COLUMN tool_key;
+ALTER TABLE projects_progress DROP COLUMN step_key;
@@ -0,0 +1,2 @@
+ALTER TABLE projects_progress ADD COLUMN step_key TEXT;
+ALTER TABLE projects_progress ADD COLUMN tool_key TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE projects DROP COLUMN content;
+ALTER TABLE projects DROP COLUMN content_hash;
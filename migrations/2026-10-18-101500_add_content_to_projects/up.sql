@@ -0,0 +1,2 @@
+ALTER TABLE projects ADD COLUMN content_hash TEXT;
+ALTER TABLE projects ADD COLUMN content TEXT;
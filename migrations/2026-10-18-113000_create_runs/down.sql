@@ -0,0 +1,2 @@
+DROP TABLE tool_runs;
+DROP TABLE runs;
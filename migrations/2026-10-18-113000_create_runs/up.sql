@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER NOT NULL PRIMARY KEY,
+    project_id INTEGER NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT,
+    status TEXT NOT NULL,
+    error TEXT,
+    FOREIGN KEY (project_id) REFERENCES projects(id)
+);
+
+CREATE TABLE IF NOT EXISTS tool_runs (
+    id INTEGER NOT NULL PRIMARY KEY,
+    run_id INTEGER NOT NULL,
+    step INTEGER NOT NULL,
+    tool INTEGER NOT NULL,
+    tool_step INTEGER NOT NULL,
+    tool_name TEXT NOT NULL,
+    started_at BIGINT NOT NULL,
+    finished_at BIGINT,
+    status TEXT NOT NULL,
+    error TEXT,
+    FOREIGN KEY (run_id) REFERENCES runs(id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS project_inputs (
+    id INTEGER NOT NULL PRIMARY KEY,
+    project_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    FOREIGN KEY (project_id) REFERENCES projects(id),
+    UNIQUE (project_id, name)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS completed_steps (
+    id INTEGER NOT NULL PRIMARY KEY,
+    project_id INTEGER NOT NULL,
+    step_key TEXT NOT NULL,
+    completed_at BIGINT NOT NULL,
+    FOREIGN KEY (project_id) REFERENCES projects(id),
+    UNIQUE (project_id, step_key)
+);
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::{env, fmt};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::runner::{CommandRunner, CommandSpec};
+
+/// A single value or a list of values, any of which matches
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn values(&self) -> Vec<&str> {
+        match self {
+            OneOrMany::One(value) => vec![value.as_str()],
+            OneOrMany::Many(values) => values.iter().map(|v| v.as_str()).collect(),
+        }
+    }
+
+    fn matches(&self, actual: &str) -> bool {
+        self.values().iter().any(|v| v.eq_ignore_ascii_case(actual))
+    }
+}
+
+impl fmt::Display for OneOrMany {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.values().join(" or "))
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum EnvCondition {
+    /// `true` if the variable must be set, `false` if it must not
+    Set(bool),
+    /// The exact value the variable must have
+    Equals(String),
+}
+
+/// Decides whether a step or tool runs on this machine. Every key that is
+/// set must match.
+#[derive(Serialize, Deserialize, JsonSchema, Default, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Operating system, e.g. `macos` or `linux`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<OneOrMany>,
+    /// CPU architecture, e.g. `aarch64` for Apple Silicon or `x86_64`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<OneOrMany>,
+    /// Hostname of the machine, compared ignoring case
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<OneOrMany>,
+    /// Environment variables that must be set, unset or have a value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, EnvCondition>>,
+    /// Commands that must be installed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<OneOrMany>,
+}
+
+fn get_hostname(runner: &dyn CommandRunner) -> Option<String> {
+    runner
+        .get_output(&CommandSpec::new("hostname"))
+        .map(|hostname| hostname.trim().to_string())
+}
+
+impl Condition {
+    /// Whether this machine matches every part of the condition
+    pub fn matches(&self, runner: &dyn CommandRunner) -> bool {
+        if let Some(os) = &self.os {
+            if !os.matches(env::consts::OS) {
+                return false;
+            }
+        }
+        if let Some(arch) = &self.arch {
+            if !arch.matches(env::consts::ARCH) {
+                return false;
+            }
+        }
+        if let Some(hostname) = &self.hostname {
+            match get_hostname(runner) {
+                Some(actual) if hostname.matches(&actual) => {}
+                _ => return false,
+            }
+        }
+        for (name, condition) in self.env.iter().flatten() {
+            let value = env::var(name).ok();
+            let matches = match condition {
+                EnvCondition::Set(set) => value.is_some() == *set,
+                EnvCondition::Equals(expected) => value.as_ref() == Some(expected),
+            };
+            if !matches {
+                return false;
+            }
+        }
+        if let Some(command) = &self.command {
+            if !command.values().iter().all(|c| runner.has_command(c)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(os) = &self.os {
+            parts.push(format!("os is {}", os));
+        }
+        if let Some(arch) = &self.arch {
+            parts.push(format!("arch is {}", arch));
+        }
+        if let Some(hostname) = &self.hostname {
+            parts.push(format!("hostname is {}", hostname));
+        }
+        for (name, condition) in self.env.iter().flatten() {
+            parts.push(match condition {
+                EnvCondition::Set(true) => format!("${} is set", name),
+                EnvCondition::Set(false) => format!("${} is not set", name),
+                EnvCondition::Equals(value) => format!("${} is \"{}\"", name, value),
+            });
+        }
+        if let Some(command) = &self.command {
+            let commands = command.values().join(", ");
+            parts.push(format!("{} installed", commands));
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+
+    #[test]
+    fn matches_the_current_platform() {
+        let runner = ScriptedRunner::new();
+        let current = format!(
+            "os: {}\narch: [other, {}]",
+            env::consts::OS,
+            env::consts::ARCH
+        );
+        let matches = |yaml: &str| {
+            serde_yaml::from_str::<Condition>(yaml)
+                .unwrap()
+                .matches(&runner)
+        };
+        assert!(matches(&current));
+        assert!(!matches("os: plan9"));
+        assert!(Condition::default().matches(&runner));
+    }
+
+    #[test]
+    fn checks_hostname_and_commands() {
+        let runner = ScriptedRunner::new()
+            .on("hostname", 0, "Build-Box\n", "")
+            .on("which nvm", 1, "", "");
+        let matches = |yaml: &str| {
+            serde_yaml::from_str::<Condition>(yaml)
+                .unwrap()
+                .matches(&runner)
+        };
+        assert!(matches("hostname: build-box\ncommand: git"));
+        assert!(!matches("hostname: laptop"));
+        assert!(!matches("command: [git, nvm]"));
+    }
+
+    #[test]
+    fn describes_every_part() {
+        let condition: Condition = serde_yaml::from_str(
+            "os: [macos, linux]\nenv:\n  CI: false\n  SHELL: /bin/zsh\ncommand: brew",
+        )
+        .unwrap();
+        assert_eq!(
+            condition.to_string(),
+            "os is macos or linux and $CI is not set and $SHELL is \"/bin/zsh\" and brew installed"
+        );
+    }
+}
@@ -1,55 +1,128 @@
 use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{env, path::Path};
 
 use diesel::prelude::*;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
-use crate::models::{NewProject, NewProjectProgress, Project, ProjectProgress};
+use crate::engine::ProgressStore;
+use crate::error::{Error, Result};
+use crate::models::{
+    CompletedStep, NewCompletedStep, NewProject, NewProjectInput, NewProjectProgress,
+    NewProjectRun, NewToolRun, Project, ProjectInput, ProjectProgress, ProjectRun, ToolRun,
+};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 pub struct Db {
     conn: SqliteConnection,
 }
 
+pub fn get_cache_path() -> Result<String> {
+    let home = env::var("HOME")
+        .map_err(|_| Error::Database("HOME is not set, cannot find the cache dir".to_string()))?;
+    Ok(format!("{}/.cache", home))
+}
+
+fn get_database_url() -> Result<String> {
+    Ok(format!("{}/siu.sqlite", get_cache_path()?))
+}
+
+/// Current time as a unix timestamp in milliseconds
+pub fn get_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
 impl Db {
-    pub fn new() -> Self {
-        let home = env::var("HOME").unwrap();
-        let cache_path = format!("{}/.cache", home);
+    pub fn new() -> Result<Self> {
+        let cache_path = get_cache_path()?;
         let cache_path_exists = Path::new(&cache_path).is_dir();
         if !cache_path_exists {
-            match fs::create_dir_all(&cache_path) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("Failed to create .cache dir: {}", e);
-                }
-            }
+            fs::create_dir_all(&cache_path)
+                .map_err(|e| Error::Database(format!("Failed to create .cache dir: {}", e)))?;
         }
-        let database_url = format!("{}/siu.sqlite", &cache_path);
+        let database_url = get_database_url()?;
 
         let conn = SqliteConnection::establish(&database_url)
-            .unwrap_or_else(|_| panic!("Error connecting to {}", database_url));
+            .map_err(|e| Error::Database(format!("Error connecting to {}: {}", database_url, e)))?;
 
-        Self { conn }
+        Ok(Self { conn })
     }
 
-    pub fn run_migrations(&mut self) {
-        use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-
-        const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+    /// Opens the database read-only if it already exists, without creating
+    /// the cache dir or the sqlite file. Used by read-only commands, which
+    /// cannot migrate a database created by an older version.
+    pub fn open_existing() -> Result<Option<Self>> {
+        let database_path = get_database_url()?;
+        if !Path::new(&database_path).is_file() {
+            return Ok(None);
+        }
+        let database_url = format!("file:{}?mode=ro", database_path);
+        let conn = SqliteConnection::establish(&database_url).map_err(|e| {
+            Error::Database(format!("Error connecting to {}: {}", database_path, e))
+        })?;
+        let mut db = Self { conn };
+        // Also fails when the migrations table is missing, it cannot be
+        // created read-only
+        if db.conn.has_pending_migration(MIGRATIONS).unwrap_or(true) {
+            return Err(Error::Database(format!(
+                "{} was created by an older version of siu, run a project once to update it",
+                database_path
+            )));
+        }
+        Ok(Some(db))
+    }
 
+    pub fn run_migrations(&mut self) -> Result<()> {
         self.conn
             .run_pending_migrations(MIGRATIONS)
-            .expect("Failed to run migrations");
+            .map_err(|e| Error::Database(format!("Failed to run migrations: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn find_project_progress(&mut self, proj: &Project) -> Result<Option<ProjectProgress>> {
+        ProjectProgress::belonging_to(proj)
+            .select(ProjectProgress::as_select())
+            .first(&mut self.conn)
+            .optional()
+            .map_err(Error::from)
     }
 
-    pub fn get_project(&mut self, project_name: &str) -> Option<Project> {
+    /// Returns the latest runs of a project, newest first
+    pub fn get_runs(&mut self, proj: &Project, limit: i64) -> Result<Vec<ProjectRun>> {
+        use crate::schema::runs::dsl::*;
+        ProjectRun::belonging_to(proj)
+            .select(ProjectRun::as_select())
+            .order(started_at.desc())
+            .limit(limit)
+            .load(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    pub fn get_tool_runs(&mut self, run: &ProjectRun) -> Result<Vec<ToolRun>> {
+        use crate::schema::tool_runs::dsl::*;
+        ToolRun::belonging_to(run)
+            .select(ToolRun::as_select())
+            .order(id.asc())
+            .load(&mut self.conn)
+            .map_err(Error::from)
+    }
+}
+
+impl ProgressStore for Db {
+    fn get_project(&mut self, project_name: &str) -> Result<Option<Project>> {
         use crate::schema::projects::dsl::*;
         projects
             .filter(name.eq(project_name))
             .first(&mut self.conn)
             .optional()
-            .unwrap()
+            .map_err(Error::from)
     }
 
-    pub fn create_project(&mut self, project_name: &str, project_path: &str) -> Project {
+    fn create_project(&mut self, project_name: &str, project_path: &str) -> Result<Project> {
         use crate::schema::projects;
         let new_project = NewProject {
             name: project_name,
@@ -60,17 +133,32 @@ impl Db {
             .values(&new_project)
             .returning(Project::as_returning())
             .get_result(&mut self.conn)
-            .expect("Error saving new project")
+            .map_err(Error::from)
     }
 
-    pub fn get_project_progress(&mut self, proj: &Project) -> ProjectProgress {
+    fn update_project_content(
+        &mut self,
+        proj: &Project,
+        project_content_hash: &str,
+        project_content: &str,
+    ) -> Result<Project> {
+        use crate::schema::projects::dsl::*;
+        diesel::update(proj)
+            .set((
+                content_hash.eq(project_content_hash),
+                content.eq(project_content),
+            ))
+            .get_result(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    fn get_project_progress(&mut self, proj: &Project) -> Result<ProjectProgress> {
         use crate::schema::projects_progress::dsl::*;
         match ProjectProgress::belonging_to(proj)
             .first(&mut self.conn)
-            .optional()
-            .unwrap()
+            .optional()?
         {
-            Some(p) => p,
+            Some(p) => Ok(p),
             None => {
                 let new_project_progress = NewProjectProgress {
                     project_id: proj.id,
@@ -78,39 +166,167 @@ impl Db {
                     tool: 0,
                     tool_step: 0,
                 };
-                diesel::insert_into(projects_progress)
+                Ok(diesel::insert_into(projects_progress)
                     .values(&new_project_progress)
-                    .get_result(&mut self.conn)
-                    .expect("Error saving new project progress")
+                    .get_result(&mut self.conn)?)
             }
         }
     }
 
-    pub fn update_project_progress(
+    fn update_project_progress(
         &mut self,
         proj: &Project,
         step: &i32,
         tool: &i32,
         tool_step: &i32,
-    ) -> ProjectProgress {
+        step_key: Option<&str>,
+        tool_key: Option<&str>,
+    ) -> Result<ProjectProgress> {
         use crate::schema::projects_progress;
         let progress = ProjectProgress::belonging_to(proj)
             .select(ProjectProgress::as_select())
-            .first(&mut self.conn)
-            .unwrap();
+            .first(&mut self.conn)?;
         diesel::update(&progress)
             .set((
                 projects_progress::step.eq(step),
                 projects_progress::tool.eq(tool),
                 projects_progress::tool_step.eq(tool_step),
+                projects_progress::step_key.eq(step_key),
+                projects_progress::tool_key.eq(tool_key),
             ))
             .get_result(&mut self.conn)
-            .unwrap()
+            .map_err(Error::from)
+    }
+
+    fn create_run(&mut self, proj: &Project) -> Result<ProjectRun> {
+        use crate::schema::runs;
+        let new_run = NewProjectRun {
+            project_id: proj.id,
+            started_at: get_timestamp(),
+            status: "running",
+        };
+        diesel::insert_into(runs::table)
+            .values(&new_run)
+            .returning(ProjectRun::as_returning())
+            .get_result(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    fn finish_run(
+        &mut self,
+        run: &ProjectRun,
+        run_status: &str,
+        run_error: Option<&str>,
+    ) -> Result<()> {
+        use crate::schema::runs::dsl::*;
+        diesel::update(run)
+            .set((
+                finished_at.eq(get_timestamp()),
+                status.eq(run_status),
+                error.eq(run_error),
+            ))
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn create_tool_run(
+        &mut self,
+        run: &ProjectRun,
+        step: &i32,
+        tool: &i32,
+        tool_step: &i32,
+        tool_name: &str,
+    ) -> Result<ToolRun> {
+        use crate::schema::tool_runs;
+        let new_tool_run = NewToolRun {
+            run_id: run.id,
+            step: *step,
+            tool: *tool,
+            tool_step: *tool_step,
+            tool_name,
+            started_at: get_timestamp(),
+            status: "running",
+        };
+        diesel::insert_into(tool_runs::table)
+            .values(&new_tool_run)
+            .returning(ToolRun::as_returning())
+            .get_result(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    fn finish_tool_run(
+        &mut self,
+        tool_run: &ToolRun,
+        tool_status: &str,
+        tool_error: Option<&str>,
+    ) -> Result<()> {
+        use crate::schema::tool_runs::dsl::*;
+        diesel::update(tool_run)
+            .set((
+                finished_at.eq(get_timestamp()),
+                status.eq(tool_status),
+                error.eq(tool_error),
+            ))
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn get_project_inputs(&mut self, proj: &Project) -> Result<Vec<ProjectInput>> {
+        use crate::schema::project_inputs::dsl::*;
+        ProjectInput::belonging_to(proj)
+            .select(ProjectInput::as_select())
+            .order(id.asc())
+            .load(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    fn set_project_input(
+        &mut self,
+        proj: &Project,
+        input_name: &str,
+        input_value: &str,
+    ) -> Result<()> {
+        use crate::schema::project_inputs;
+        let input = NewProjectInput {
+            project_id: proj.id,
+            name: input_name,
+            value: input_value,
+            updated_at: get_timestamp(),
+        };
+        diesel::insert_into(project_inputs::table)
+            .values(&input)
+            .on_conflict((project_inputs::project_id, project_inputs::name))
+            .do_update()
+            .set(&input)
+            .execute(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn get_completed_steps(&mut self, proj: &Project) -> Result<Vec<CompletedStep>> {
+        use crate::schema::completed_steps::dsl::*;
+        CompletedStep::belonging_to(proj)
+            .select(CompletedStep::as_select())
+            .order(id.asc())
+            .load(&mut self.conn)
+            .map_err(Error::from)
+    }
+
+    fn complete_step(&mut self, proj: &Project, key: &str) -> Result<()> {
+        use crate::schema::completed_steps;
+        let completed_step = NewCompletedStep {
+            project_id: proj.id,
+            step_key: key,
+            completed_at: get_timestamp(),
+        };
+        diesel::insert_or_ignore_into(completed_steps::table)
+            .values(&completed_step)
+            .execute(&mut self.conn)?;
+        Ok(())
     }
-}
 
-impl Default for Db {
-    fn default() -> Self {
-        Self::new()
+    fn clear_completed_steps(&mut self, proj: &Project) -> Result<()> {
+        use crate::schema::completed_steps::dsl::*;
+        diesel::delete(completed_steps.filter(project_id.eq(proj.id))).execute(&mut self.conn)?;
+        Ok(())
     }
 }
@@ -1,20 +1,77 @@
 use difference::{Changeset, Difference};
 use owo_colors::OwoColorize;
 
-pub fn print_diff(text1: &str, text2: &str) {
+/// Unchanged lines shown around every change
+const CONTEXT: usize = 3;
+
+/// Lines of a diff between `text1` and `text2`, prefixed with `+`, `-` or a
+/// space. Unchanged lines far from any change are left out, `...` marks
+/// where.
+fn get_diff_lines(text1: &str, text2: &str) -> Vec<String> {
     let Changeset { diffs, .. } = Changeset::new(text1, text2, "\n");
+    let lines: Vec<(char, &str)> = diffs
+        .iter()
+        .flat_map(|diff| {
+            let (prefix, text) = match diff {
+                Difference::Same(x) => (' ', x),
+                Difference::Add(x) => ('+', x),
+                Difference::Rem(x) => ('-', x),
+            };
+            text.split('\n').map(move |line| (prefix, line))
+        })
+        .collect();
+    let changes: Vec<usize> = (0..lines.len()).filter(|i| lines[*i].0 != ' ').collect();
+    let is_shown = |i: usize| changes.iter().any(|c| c.abs_diff(i) <= CONTEXT);
 
-    for diff in diffs {
-        match diff {
-            Difference::Same(ref x) => {
-                println!("{}", x);
-            }
-            Difference::Add(ref x) => {
-                print!("{} ", x.green());
-            }
-            Difference::Rem(ref x) => {
-                println!("-{}", x.red());
-            }
+    let mut output = Vec::new();
+    let mut skipped = false;
+    for (i, (prefix, line)) in lines.iter().enumerate() {
+        if !is_shown(i) {
+            skipped = true;
+            continue;
         }
+        if skipped {
+            output.push("...".to_string());
+            skipped = false;
+        }
+        output.push(format!("{}{}", prefix, line));
+    }
+    if skipped {
+        output.push("...".to_string());
+    }
+    output
+}
+
+pub fn print_diff(text1: &str, text2: &str) {
+    for line in get_diff_lines(text1, text2) {
+        match line.chars().next() {
+            Some('+') => println!("{}", line.green()),
+            Some('-') => println!("{}", line.red()),
+            _ => println!("{}", line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_every_changed_line() {
+        assert_eq!(
+            get_diff_lines("a\nb\nc", "a\nx\ny\nc"),
+            vec![" a", "-b", "+x", "+y", " c"]
+        );
+    }
+
+    #[test]
+    fn shows_only_the_lines_around_changes() {
+        let old: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[9] = "ten".to_string();
+        assert_eq!(
+            get_diff_lines(&old.join("\n"), &new.join("\n")),
+            vec!["...", " 7", " 8", " 9", "-10", "+ten", " 11", " 12", " 13", "..."]
+        );
     }
 }
@@ -0,0 +1,870 @@
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+use crate::events::{Event, Observer};
+use crate::models::{CompletedStep, Project, ProjectInput, ProjectProgress, ProjectRun, ToolRun};
+use crate::projects::{get_content_hash, Position, ProjectConfiguration, ToolConfiguration};
+use crate::tools::runner::CommandRunner;
+use crate::tools::types::Context;
+use crate::ui::Ui;
+use crate::vars;
+
+/// Where the engine keeps each project's progress and run history. `Db` is
+/// the implementation used by the CLI.
+pub trait ProgressStore {
+    fn get_project(&mut self, name: &str) -> Result<Option<Project>>;
+    fn create_project(&mut self, name: &str, path: &str) -> Result<Project>;
+    /// Stores the project file used by the current run
+    fn update_project_content(
+        &mut self,
+        project: &Project,
+        content_hash: &str,
+        content: &str,
+    ) -> Result<Project>;
+    /// Returns the saved progress, starting at the first step if there is none
+    fn get_project_progress(&mut self, project: &Project) -> Result<ProjectProgress>;
+    fn update_project_progress(
+        &mut self,
+        project: &Project,
+        step: &i32,
+        tool: &i32,
+        tool_step: &i32,
+        step_key: Option<&str>,
+        tool_key: Option<&str>,
+    ) -> Result<ProjectProgress>;
+    fn create_run(&mut self, project: &Project) -> Result<ProjectRun>;
+    fn finish_run(&mut self, run: &ProjectRun, status: &str, error: Option<&str>) -> Result<()>;
+    fn create_tool_run(
+        &mut self,
+        run: &ProjectRun,
+        step: &i32,
+        tool: &i32,
+        tool_step: &i32,
+        tool_name: &str,
+    ) -> Result<ToolRun>;
+    fn finish_tool_run(
+        &mut self,
+        tool_run: &ToolRun,
+        status: &str,
+        error: Option<&str>,
+    ) -> Result<()>;
+    /// Returns the stored answers to the project's `inputs`
+    fn get_project_inputs(&mut self, project: &Project) -> Result<Vec<ProjectInput>>;
+    fn set_project_input(&mut self, project: &Project, name: &str, value: &str) -> Result<()>;
+    /// Returns the steps completed since the project was last started from
+    /// the beginning
+    fn get_completed_steps(&mut self, project: &Project) -> Result<Vec<CompletedStep>>;
+    /// Records the step with `step_key` as completed, once
+    fn complete_step(&mut self, project: &Project, step_key: &str) -> Result<()>;
+    fn clear_completed_steps(&mut self, project: &Project) -> Result<()>;
+}
+
+/// How a run that did not fail ended
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RunOutcome {
+    /// Every step is done
+    Completed,
+    /// A tool needs the user to do something first, e.g. open a new shell,
+    /// and the project has to be run again to continue
+    Paused,
+}
+
+/// Runs projects, resuming from and saving progress to a `ProgressStore`
+pub struct Engine<'a> {
+    store: &'a mut dyn ProgressStore,
+    runner: &'a dyn CommandRunner,
+    ui: &'a dyn Ui,
+    observers: Vec<&'a dyn Observer>,
+    overrides: BTreeMap<String, String>,
+}
+
+impl<'a> Engine<'a> {
+    pub fn new(
+        store: &'a mut dyn ProgressStore,
+        runner: &'a dyn CommandRunner,
+        ui: &'a dyn Ui,
+    ) -> Self {
+        Self {
+            store,
+            runner,
+            ui,
+            observers: Vec::new(),
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Sets variables that take precedence over the project's `vars` and
+    /// inputs, e.g. from `--set name=value`
+    pub fn with_overrides(mut self, overrides: BTreeMap<String, String>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Sends the events of every run to `observer`, on top of the UI
+    pub fn subscribe(&mut self, observer: &'a dyn Observer) {
+        self.observers.push(observer);
+    }
+
+    fn emit(&self, event: Event) {
+        self.ui.on_event(&event);
+        for observer in &self.observers {
+            observer.on_event(&event);
+        }
+    }
+
+    /// Runs a project from its saved progress until it completes, pauses or
+    /// fails.
+    pub fn run(&mut self, config: &ProjectConfiguration) -> Result<RunOutcome> {
+        let project = config.options.name.clone();
+        self.emit(Event::ProjectStarted {
+            project: project.clone(),
+            description: config.options.description.clone(),
+            steps: config.options.steps.len(),
+        });
+        match self.run_project(config) {
+            Ok(RunOutcome::Completed) => {
+                self.emit(Event::ProjectCompleted { project });
+                Ok(RunOutcome::Completed)
+            }
+            Ok(RunOutcome::Paused) => {
+                self.emit(Event::ProjectPaused { project });
+                Ok(RunOutcome::Paused)
+            }
+            Err(e) => {
+                self.emit(Event::ProjectFailed {
+                    project,
+                    error: e.to_string(),
+                    exit_code: e.exit_code(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    fn run_project(&mut self, config: &ProjectConfiguration) -> Result<RunOutcome> {
+        let (project, progress) = self.get_project_progress(config)?;
+        let config = &self.resolve_inputs(config, &project)?;
+        self.update_content(config, &project)?;
+        let position = match config.get_start_position(Some(&progress)) {
+            Ok(position) => position,
+            Err(saved) => self.ask_start_position(config, &project, saved)?,
+        };
+        let completed = self.get_completed_steps(config, &project, position)?;
+        if position != (0, 0, 0) {
+            self.emit(Event::ProjectResumed {
+                step: position.0,
+                tool: position.1,
+            });
+        }
+        let new_steps = config.get_new_steps(position, &completed);
+        if !new_steps.is_empty() {
+            let steps: Vec<String> = new_steps
+                .iter()
+                .map(|i| format!("{} \"{}\"", i + 1, config.options.steps[*i].description))
+                .collect();
+            self.ui.warning(&format!(
+                "Steps were added before the one you stopped at, running them first: {}",
+                steps.join(", ")
+            ));
+        }
+        let run_record = self.store.create_run(&project)?;
+        match self.run_steps(config, &project, &run_record, position, &completed) {
+            Ok(outcome) => {
+                if outcome == RunOutcome::Completed {
+                    self.store.clear_completed_steps(&project)?;
+                }
+                let status = match outcome {
+                    RunOutcome::Completed => "completed",
+                    RunOutcome::Paused => "paused",
+                };
+                self.store.finish_run(&run_record, status, None)?;
+                Ok(outcome)
+            }
+            Err(e) => {
+                self.store
+                    .finish_run(&run_record, "failed", Some(&e.to_string()))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Forgets the saved progress so the next run starts from the first step
+    pub fn reset(&mut self, config: &ProjectConfiguration) -> Result<()> {
+        let Some(project) = self.store.get_project(&config.options.name)? else {
+            return Err(Error::Project(format!(
+                "\"{}\" has not been run yet, nothing to reset",
+                config.options.name
+            )));
+        };
+        self.store
+            .update_project_progress(&project, &0, &0, &0, None, None)?;
+        self.store.clear_completed_steps(&project)?;
+        Ok(())
+    }
+
+    /// Asks every input of the project again, suggesting the stored answers
+    pub fn edit_inputs(&mut self, config: &ProjectConfiguration) -> Result<()> {
+        let (project, _) = self.get_project_progress(config)?;
+        let answers = self.get_answers(&project)?;
+        for input in &config.options.inputs {
+            let default = answers.get(&input.name).or(input.default.as_ref());
+            let value = self.ui.input(input, default.map(|d| d.as_str()))?;
+            self.store
+                .set_project_input(&project, &input.name, &value)?;
+        }
+        Ok(())
+    }
+
+    fn get_answers(&mut self, project: &Project) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .store
+            .get_project_inputs(project)?
+            .into_iter()
+            .map(|input| (input.name, input.value))
+            .collect())
+    }
+
+    /// Asks for the inputs that were not answered yet and fills the answers
+    /// into the project. Inputs set with `--set` or the environment are not
+    /// asked.
+    fn resolve_inputs(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+    ) -> Result<ProjectConfiguration> {
+        let mut answers = self.get_answers(project)?;
+        for input in &config.options.inputs {
+            if answers.contains_key(&input.name)
+                || vars::get_override(&input.name, &self.overrides).is_some()
+            {
+                continue;
+            }
+            let value = self.ui.input(input, input.default.as_deref())?;
+            self.store.set_project_input(project, &input.name, &value)?;
+            answers.insert(input.name.clone(), value);
+        }
+        config.with_vars(&answers, &self.overrides)
+    }
+
+    fn save_progress(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+        (step, tool, tool_step): Position,
+    ) -> Result<ProjectProgress> {
+        let step_config = config.options.steps.get(step);
+        let step_key = step_config.map(|s| s.get_key());
+        let tool_key = step_config
+            .and_then(|s| s.run.get(tool))
+            .map(|t| t.get_key());
+        self.store.update_project_progress(
+            project,
+            &(step as i32),
+            &(tool as i32),
+            &(tool_step as i32),
+            step_key.as_deref(),
+            tool_key.as_deref(),
+        )
+    }
+
+    /// Returns the keys of the steps completed since the project was started.
+    /// Progress saved before they were recorded counts every step before
+    /// `position` as completed.
+    fn get_completed_steps(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+        position: Position,
+    ) -> Result<Vec<String>> {
+        let mut completed: Vec<String> = self
+            .store
+            .get_completed_steps(project)?
+            .into_iter()
+            .map(|c| c.step_key)
+            .collect();
+        if completed.is_empty() {
+            for step in &config.options.steps[..position.0] {
+                self.store.complete_step(project, &step.get_key())?;
+                completed.push(step.get_key());
+            }
+        }
+        Ok(completed)
+    }
+
+    /// Runs every step from `position` on, and the earlier ones that are not
+    /// `completed`. Completed steps after `position` are skipped, there are
+    /// some when a step added before them paused the last run.
+    fn run_steps(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+        run_record: &ProjectRun,
+        position: Position,
+        completed: &[String],
+    ) -> Result<RunOutcome> {
+        for (index, step) in config.options.steps.iter().enumerate() {
+            if config.is_step_done(index, position, completed) {
+                if index >= position.0 {
+                    self.emit(Event::StepSkipped {
+                        step: index,
+                        description: step.description.clone(),
+                        reason: "already done".to_string(),
+                    });
+                }
+                continue;
+            }
+            let start = match index == position.0 {
+                true => position,
+                false => (index, 0, 0),
+            };
+            if self.run_step(config, project, run_record, start)? {
+                return Ok(RunOutcome::Paused);
+            }
+            // Back to where the last run stopped after a step added before it
+            if index < position.0 {
+                self.save_progress(config, project, position)?;
+            }
+        }
+        Ok(RunOutcome::Completed)
+    }
+
+    /// Runs the step at `index` from the given tool, returns whether a tool
+    /// paused the run
+    fn run_step(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+        run_record: &ProjectRun,
+        (index, tool, tool_step): Position,
+    ) -> Result<bool> {
+        let step = &config.options.steps[index];
+        let step_skipped = match &step.when {
+            Some(when) if !when.matches(self.runner) => {
+                self.emit(Event::StepSkipped {
+                    step: index,
+                    description: step.description.clone(),
+                    reason: format!("only runs when {}", when),
+                });
+                true
+            }
+            _ => {
+                self.emit(Event::StepStarted {
+                    step: index,
+                    description: step.description.clone(),
+                });
+                false
+            }
+        };
+
+        let tools: Vec<ToolConfiguration> = step.run.clone().drain(tool..).collect();
+        let observers = self.observers.clone();
+        let ctx = Context {
+            runner: self.runner,
+            ui: self.ui,
+            observers: &observers,
+        };
+
+        for (offset, run) in tools.into_iter().enumerate() {
+            let tool = tool + offset;
+            let tool_step = if offset == 0 { tool_step } else { 0 };
+            let name = run.tool.name().to_string();
+            let tool_run = self.store.create_tool_run(
+                run_record,
+                &(index as i32),
+                &(tool as i32),
+                &(tool_step as i32),
+                run.tool.name(),
+            )?;
+            // The tools of a skipped step are recorded as skipped without an
+            // event each
+            let skip_reason = match &run.when {
+                _ if step_skipped => None,
+                Some(when) if !when.matches(self.runner) => {
+                    Some(format!("only runs when {}", when))
+                }
+                _ if run.tool.check(self.runner) => Some("already done".to_string()),
+                _ => None,
+            };
+            if step_skipped || skip_reason.is_some() {
+                if let Some(reason) = skip_reason {
+                    self.emit(Event::ToolSkipped {
+                        step: index,
+                        tool,
+                        name,
+                        reason,
+                    });
+                }
+                self.store.finish_tool_run(&tool_run, "skipped", None)?;
+                self.save_progress(config, project, (index, tool + 1, 0))?;
+                continue;
+            }
+            self.emit(Event::ToolStarted {
+                step: index,
+                tool,
+                name: name.clone(),
+            });
+            match run.tool.install(&ctx, tool_step) {
+                Ok(pause) => {
+                    if pause {
+                        self.emit(Event::ToolPaused {
+                            step: index,
+                            tool,
+                            name,
+                        });
+                        self.store.finish_tool_run(&tool_run, "paused", None)?;
+                        self.save_progress(config, project, (index, tool, tool_step + 1))?;
+                        return Ok(true);
+                    }
+                }
+                // The user chose to stop, e.g. at a pause, the tool runs
+                // again next time
+                Err(Error::Aborted(message)) => {
+                    self.ui.info(&message);
+                    self.emit(Event::ToolPaused {
+                        step: index,
+                        tool,
+                        name,
+                    });
+                    self.store
+                        .finish_tool_run(&tool_run, "paused", Some(&message))?;
+                    self.save_progress(config, project, (index, tool, tool_step))?;
+                    return Ok(true);
+                }
+                Err(e) => {
+                    self.emit(Event::ToolFailed {
+                        step: index,
+                        tool,
+                        name,
+                        error: e.to_string(),
+                    });
+                    self.store
+                        .finish_tool_run(&tool_run, "failed", Some(&e.to_string()))?;
+                    return Err(e);
+                }
+            }
+            self.emit(Event::ToolFinished {
+                step: index,
+                tool,
+                name,
+            });
+            self.store.finish_tool_run(&tool_run, "done", None)?;
+            self.save_progress(config, project, (index, tool + 1, 0))?;
+        }
+        self.store.complete_step(project, &step.get_key())?;
+        self.save_progress(config, project, (index + 1, 0, 0))?;
+        Ok(false)
+    }
+
+    fn get_project_progress(
+        &mut self,
+        config: &ProjectConfiguration,
+    ) -> Result<(Project, ProjectProgress)> {
+        let project = match self.store.get_project(&config.options.name)? {
+            Some(project) => project,
+            None => self
+                .store
+                .create_project(&config.options.name, config.get_source())?,
+        };
+        let progress = self.store.get_project_progress(&project)?;
+        Ok((project, progress))
+    }
+
+    /// Asks what to do when the saved position no longer exists in the
+    /// project file.
+    fn ask_start_position(
+        &mut self,
+        config: &ProjectConfiguration,
+        project: &Project,
+        saved: Position,
+    ) -> Result<Position> {
+        let (step, _, _) = saved;
+        self.ui.warning(
+            "The project file changed since your last run and the step you stopped at could not be found.",
+        );
+        let items = [
+            format!(
+                "Continue at step {}: {}",
+                step + 1,
+                config.options.steps[step].description
+            ),
+            "Start from the beginning".to_string(),
+            "Exit".to_string(),
+        ];
+        match self.ui.select(
+            "How do you want to continue?",
+            &items,
+            "The saved progress does not match the project file",
+        )? {
+            Some(0) => Ok(saved),
+            Some(1) => {
+                self.store.clear_completed_steps(project)?;
+                Ok((0, 0, 0))
+            }
+            _ => Err(Error::Aborted("Exited without running".to_string())),
+        }
+    }
+
+    /// Shows what changed in the project file since the last run and stores
+    /// the current contents for the next one.
+    fn update_content(&mut self, config: &ProjectConfiguration, project: &Project) -> Result<()> {
+        let content_hash = get_content_hash(&config.source);
+        if project.content_hash.as_ref() == Some(&content_hash) {
+            return Ok(());
+        }
+        if let Some(content) = &project.content {
+            self.ui.project_changed(content, &config.source);
+        }
+        self.store
+            .update_project_content(project, &content_hash, &config.source)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::inputs::Input;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    /// Keeps the progress of a single project in memory
+    #[derive(Default)]
+    struct MemoryStore {
+        project: Option<Project>,
+        progress: Option<ProjectProgress>,
+        runs: Vec<ProjectRun>,
+        tool_runs: Vec<ToolRun>,
+        inputs: Vec<ProjectInput>,
+        completed_steps: Vec<CompletedStep>,
+    }
+
+    impl ProgressStore for MemoryStore {
+        fn get_project(&mut self, name: &str) -> Result<Option<Project>> {
+            Ok(self.project.clone().filter(|p| p.name == name))
+        }
+
+        fn create_project(&mut self, name: &str, path: &str) -> Result<Project> {
+            let project = Project {
+                id: 1,
+                name: name.to_string(),
+                path: path.to_string(),
+                content_hash: None,
+                content: None,
+            };
+            self.project = Some(project.clone());
+            Ok(project)
+        }
+
+        fn update_project_content(
+            &mut self,
+            project: &Project,
+            content_hash: &str,
+            content: &str,
+        ) -> Result<Project> {
+            let project = Project {
+                content_hash: Some(content_hash.to_string()),
+                content: Some(content.to_string()),
+                ..project.clone()
+            };
+            self.project = Some(project.clone());
+            Ok(project)
+        }
+
+        fn get_project_progress(&mut self, project: &Project) -> Result<ProjectProgress> {
+            Ok(self.progress.clone().unwrap_or(ProjectProgress {
+                id: 1,
+                project_id: project.id,
+                step: 0,
+                tool: 0,
+                tool_step: 0,
+                step_key: None,
+                tool_key: None,
+            }))
+        }
+
+        fn update_project_progress(
+            &mut self,
+            project: &Project,
+            step: &i32,
+            tool: &i32,
+            tool_step: &i32,
+            step_key: Option<&str>,
+            tool_key: Option<&str>,
+        ) -> Result<ProjectProgress> {
+            let progress = ProjectProgress {
+                id: 1,
+                project_id: project.id,
+                step: *step,
+                tool: *tool,
+                tool_step: *tool_step,
+                step_key: step_key.map(|k| k.to_string()),
+                tool_key: tool_key.map(|k| k.to_string()),
+            };
+            self.progress = Some(progress.clone());
+            Ok(progress)
+        }
+
+        fn create_run(&mut self, project: &Project) -> Result<ProjectRun> {
+            let run = ProjectRun {
+                id: self.runs.len() as i32 + 1,
+                project_id: project.id,
+                started_at: 0,
+                finished_at: None,
+                status: "running".to_string(),
+                error: None,
+            };
+            self.runs.push(run.clone());
+            Ok(run)
+        }
+
+        fn finish_run(
+            &mut self,
+            run: &ProjectRun,
+            status: &str,
+            error: Option<&str>,
+        ) -> Result<()> {
+            let run = &mut self.runs[run.id as usize - 1];
+            run.status = status.to_string();
+            run.error = error.map(|e| e.to_string());
+            Ok(())
+        }
+
+        fn create_tool_run(
+            &mut self,
+            run: &ProjectRun,
+            step: &i32,
+            tool: &i32,
+            tool_step: &i32,
+            tool_name: &str,
+        ) -> Result<ToolRun> {
+            let tool_run = ToolRun {
+                id: self.tool_runs.len() as i32 + 1,
+                run_id: run.id,
+                step: *step,
+                tool: *tool,
+                tool_step: *tool_step,
+                tool_name: tool_name.to_string(),
+                started_at: 0,
+                finished_at: None,
+                status: "running".to_string(),
+                error: None,
+            };
+            self.tool_runs.push(tool_run.clone());
+            Ok(tool_run)
+        }
+
+        fn finish_tool_run(
+            &mut self,
+            tool_run: &ToolRun,
+            status: &str,
+            error: Option<&str>,
+        ) -> Result<()> {
+            let tool_run = &mut self.tool_runs[tool_run.id as usize - 1];
+            tool_run.status = status.to_string();
+            tool_run.error = error.map(|e| e.to_string());
+            Ok(())
+        }
+
+        fn get_project_inputs(&mut self, _: &Project) -> Result<Vec<ProjectInput>> {
+            Ok(self.inputs.clone())
+        }
+
+        fn set_project_input(&mut self, project: &Project, name: &str, value: &str) -> Result<()> {
+            self.inputs.retain(|i| i.name != name);
+            self.inputs.push(ProjectInput {
+                id: self.inputs.len() as i32 + 1,
+                project_id: project.id,
+                name: name.to_string(),
+                value: value.to_string(),
+                updated_at: 0,
+            });
+            Ok(())
+        }
+
+        fn get_completed_steps(&mut self, _: &Project) -> Result<Vec<CompletedStep>> {
+            Ok(self.completed_steps.clone())
+        }
+
+        fn complete_step(&mut self, project: &Project, step_key: &str) -> Result<()> {
+            if self.completed_steps.iter().all(|c| c.step_key != step_key) {
+                self.completed_steps.push(CompletedStep {
+                    id: self.completed_steps.len() as i32 + 1,
+                    project_id: project.id,
+                    step_key: step_key.to_string(),
+                    completed_at: 0,
+                });
+            }
+            Ok(())
+        }
+
+        fn clear_completed_steps(&mut self, _: &Project) -> Result<()> {
+            self.completed_steps.clear();
+            Ok(())
+        }
+    }
+
+    /// Stops at every pause and keeps the events it was sent
+    #[derive(Default)]
+    struct StoppingUi {
+        events: RefCell<Vec<Event>>,
+    }
+
+    impl Observer for StoppingUi {
+        fn on_event(&self, event: &Event) {
+            self.events.borrow_mut().push(event.clone());
+        }
+    }
+
+    impl Ui for StoppingUi {
+        fn project_changed(&self, _: &str, _: &str) {}
+        fn note(&self, _: &str) {}
+        fn info(&self, _: &str) {}
+        fn warning(&self, _: &str) {}
+        fn confirm(&self, prompt: &str) -> Result<bool> {
+            SilentUi.confirm(prompt)
+        }
+        fn pause(&self) -> Result<bool> {
+            Ok(false)
+        }
+        fn select(&self, prompt: &str, items: &[String], reason: &str) -> Result<Option<usize>> {
+            SilentUi.select(prompt, items, reason)
+        }
+        fn input(&self, input: &Input, default: Option<&str>) -> Result<String> {
+            SilentUi.input(input, default)
+        }
+    }
+
+    fn project(steps: &str) -> ProjectConfiguration {
+        let source = format!("name: p\ndescription: d\nsteps:\n{}", steps);
+        ProjectConfiguration {
+            options: serde_yaml::from_str(&source).unwrap(),
+            source,
+            path: None,
+            url: None,
+        }
+    }
+
+    fn position(store: &MemoryStore) -> (i32, i32, i32) {
+        let progress = store.progress.as_ref().unwrap();
+        (progress.step, progress.tool, progress.tool_step)
+    }
+
+    #[test]
+    fn stops_at_a_pause_and_resumes_after_it() {
+        let config = project(
+            "  - description: one\n    run:\n      - run: echo a\n      - pause: true\n      - run: echo b\n  - description: two\n    run:\n      - run: echo c\n",
+        );
+        let mut store = MemoryStore::default();
+        let runner = ScriptedRunner::new();
+        let ui = StoppingUi::default();
+        let result = Engine::new(&mut store, &runner, &ui).run(&config);
+        assert_eq!(result.unwrap(), RunOutcome::Paused);
+        assert_eq!(runner.argvs(), vec![vec!["sh", "-c", "echo a"]]);
+        assert_eq!(position(&store), (0, 1, 0));
+        assert_eq!(store.runs[0].status, "paused");
+        let events: Vec<Event> = ui
+            .events
+            .take()
+            .into_iter()
+            .filter(|e| !matches!(e, Event::CommandSpawned { .. } | Event::Output { .. }))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::ProjectStarted {
+                    project: "p".to_string(),
+                    description: "d".to_string(),
+                    steps: 2,
+                },
+                Event::StepStarted {
+                    step: 0,
+                    description: "one".to_string(),
+                },
+                Event::ToolStarted {
+                    step: 0,
+                    tool: 0,
+                    name: "run".to_string(),
+                },
+                Event::ToolFinished {
+                    step: 0,
+                    tool: 0,
+                    name: "run".to_string(),
+                },
+                Event::ToolStarted {
+                    step: 0,
+                    tool: 1,
+                    name: "pause".to_string(),
+                },
+                Event::ToolPaused {
+                    step: 0,
+                    tool: 1,
+                    name: "pause".to_string(),
+                },
+                Event::ProjectPaused {
+                    project: "p".to_string(),
+                },
+            ]
+        );
+        let statuses: Vec<&str> = store.tool_runs.iter().map(|t| t.status.as_str()).collect();
+        assert_eq!(statuses, vec!["done", "paused"]);
+
+        let runner = ScriptedRunner::new();
+        let result = Engine::new(&mut store, &runner, &SilentUi).run(&config);
+        assert_eq!(result.unwrap(), RunOutcome::Completed);
+        assert_eq!(
+            runner.argvs(),
+            vec![vec!["sh", "-c", "echo b"], vec!["sh", "-c", "echo c"]]
+        );
+        assert_eq!(position(&store), (2, 0, 0));
+        assert_eq!(store.progress.as_ref().unwrap().step_key, None);
+        assert_eq!(store.runs[1].status, "completed");
+    }
+
+    #[test]
+    fn runs_steps_added_before_the_saved_position_first() {
+        let steps = "  - description: one\n    run:\n      - run: echo a\n  - description: two\n    run:\n      - pause: true\n      - run: echo b\n";
+        let mut store = MemoryStore::default();
+        let runner = ScriptedRunner::new();
+        let ui = StoppingUi::default();
+        let result = Engine::new(&mut store, &runner, &ui).run(&project(steps));
+        assert_eq!(result.unwrap(), RunOutcome::Paused);
+
+        let config = project(&format!(
+            "  - description: zero\n    run:\n      - run: echo z\n{}",
+            steps
+        ));
+        let runner = ScriptedRunner::new();
+        let result = Engine::new(&mut store, &runner, &SilentUi).run(&config);
+        assert_eq!(result.unwrap(), RunOutcome::Completed);
+        assert_eq!(
+            runner.argvs(),
+            vec![vec!["sh", "-c", "echo z"], vec!["sh", "-c", "echo b"]]
+        );
+        assert!(store.completed_steps.is_empty());
+    }
+
+    #[test]
+    fn retries_the_failed_tool() {
+        let config =
+            project("  - description: one\n    run:\n      - run: echo a\n      - run: exit 1\n");
+        let mut store = MemoryStore::default();
+        let runner = ScriptedRunner::new().on("sh -c exit 1", 1, "", "boom\n");
+        match Engine::new(&mut store, &runner, &SilentUi).run(&config) {
+            Err(Error::CommandFailed { status, stderr, .. }) => {
+                assert_eq!(status, Some(1));
+                assert_eq!(stderr, "boom\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(position(&store), (0, 1, 0));
+        assert_eq!(store.runs[0].status, "failed");
+        assert_eq!(store.tool_runs[1].status, "failed");
+
+        let runner = ScriptedRunner::new();
+        let result = Engine::new(&mut store, &runner, &SilentUi).run(&config);
+        assert_eq!(result.unwrap(), RunOutcome::Completed);
+        assert_eq!(runner.argvs(), vec![vec!["sh", "-c", "exit 1"]]);
+    }
+}
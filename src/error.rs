@@ -0,0 +1,99 @@
+use std::fmt;
+
+use crate::validation::ValidationError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A project could not be found, read or fetched
+    Project(String),
+    /// A project file is not valid
+    Parse(String),
+    /// A command needed by a tool is not installed
+    MissingCommand(String),
+    /// A command exited with a non-zero status
+    CommandFailed {
+        command: String,
+        status: Option<i32>,
+        stderr: String,
+    },
+    Database(String),
+    /// User input was needed but could not be read, e.g. without a terminal
+    Input(String),
+    /// The user chose to stop, e.g. at a pause. Returned by a tool it pauses
+    /// the run instead of failing it.
+    Aborted(String),
+    Io(String),
+    /// A tool failed for another reason
+    Tool(String),
+}
+
+impl Error {
+    /// Process exit code, so wrapper scripts can tell failures apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) | Error::Tool(_) => 1,
+            // 2 is used by clap for invalid arguments
+            Error::Project(_) | Error::Parse(_) => 3,
+            Error::MissingCommand(_) => 4,
+            Error::CommandFailed { .. } => 5,
+            Error::Database(_) => 6,
+            Error::Input(_) => 7,
+            Error::Aborted(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Project(message) => write!(f, "{}", message),
+            Error::Parse(message) => write!(f, "Failed to parse project file: {}", message),
+            Error::MissingCommand(command) => write!(
+                f,
+                "Command `{}` not found, make sure it is installed and in your PATH",
+                command
+            ),
+            Error::CommandFailed {
+                command,
+                status,
+                stderr,
+            } => {
+                match status {
+                    Some(code) => write!(f, "Command `{}` exited with code {}", command, code)?,
+                    None => write!(f, "Command `{}` was terminated by a signal", command)?,
+                }
+                if !stderr.trim().is_empty() {
+                    write!(f, ":\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::Database(message) => write!(f, "Database error: {}", message),
+            Error::Input(message) => write!(f, "{}", message),
+            Error::Aborted(message) => write!(f, "{}", message),
+            Error::Io(message) => write!(f, "{}", message),
+            Error::Tool(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        Error::Database(e.to_string())
+    }
+}
+
+impl From<diesel::ConnectionError> for Error {
+    fn from(e: diesel::ConnectionError) -> Self {
+        Error::Database(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::io::Write;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::db::get_timestamp;
+
+/// Which stream of a command a line of output was written to
+#[derive(Serialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Something that happened while running a project. Step and tool indices
+/// start at 0.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ProjectStarted {
+        project: String,
+        description: String,
+        steps: usize,
+    },
+    /// The run continues from saved progress instead of the first step
+    ProjectResumed {
+        step: usize,
+        tool: usize,
+    },
+    StepStarted {
+        step: usize,
+        description: String,
+    },
+    /// The step's `when` condition does not match this machine
+    StepSkipped {
+        step: usize,
+        description: String,
+        reason: String,
+    },
+    ToolStarted {
+        step: usize,
+        tool: usize,
+        name: String,
+    },
+    /// The tool's work was already done or its `when` condition does not
+    /// match this machine
+    ToolSkipped {
+        step: usize,
+        tool: usize,
+        name: String,
+        reason: String,
+    },
+    /// A tool is about to run a command
+    CommandSpawned {
+        command: String,
+    },
+    /// A line a command wrote
+    Output {
+        stream: OutputStream,
+        line: String,
+    },
+    ToolFinished {
+        step: usize,
+        tool: usize,
+        name: String,
+    },
+    /// The tool needs the project to be run again, or the user stopped at a
+    /// pause
+    ToolPaused {
+        step: usize,
+        tool: usize,
+        name: String,
+    },
+    ToolFailed {
+        step: usize,
+        tool: usize,
+        name: String,
+        error: String,
+    },
+    ProjectCompleted {
+        project: String,
+    },
+    ProjectPaused {
+        project: String,
+    },
+    ProjectFailed {
+        project: String,
+        error: String,
+        exit_code: i32,
+    },
+}
+
+/// Receives the events of a run
+pub trait Observer {
+    fn on_event(&self, event: &Event);
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum EventFormat {
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Serialize)]
+struct EventRecord<'a> {
+    /// Unix timestamp in milliseconds
+    timestamp: i64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Writes every event as a line of JSON
+pub struct JsonEvents {
+    writer: RefCell<Box<dyn Write>>,
+}
+
+impl JsonEvents {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer: RefCell::new(writer),
+        }
+    }
+}
+
+impl Observer for JsonEvents {
+    fn on_event(&self, event: &Event) {
+        let record = EventRecord {
+            timestamp: get_timestamp(),
+            event,
+        };
+        let Ok(line) = serde_json::to_string(&record) else {
+            return;
+        };
+        let mut writer = self.writer.borrow_mut();
+        // A consumer going away must not stop the run
+        let _ = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+    }
+}
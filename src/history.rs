@@ -0,0 +1,74 @@
+use chrono::{Local, TimeZone};
+use owo_colors::OwoColorize;
+
+use crate::db::Db;
+use crate::engine::ProgressStore;
+use crate::error::Result;
+
+fn format_timestamp(timestamp: i64) -> String {
+    match Local.timestamp_millis_opt(timestamp).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+fn format_duration(started_at: i64, finished_at: Option<i64>) -> String {
+    match finished_at {
+        Some(finished_at) => format!("{:.1}s", (finished_at - started_at) as f64 / 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Colors `status` after padding it to `width`, escape codes would count
+/// towards the width otherwise
+fn format_status(status: &str, width: usize) -> String {
+    let text = format!("{:<width$}", status, width = width);
+    match status {
+        "completed" | "done" => text.green().to_string(),
+        "failed" => text.red().to_string(),
+        "paused" | "running" => text.yellow().to_string(),
+        _ => text.dimmed().to_string(),
+    }
+}
+
+/// Prints the latest runs of a project with the outcome of every tool
+pub fn print_history(project_name: &str, limit: i64) -> Result<()> {
+    let Some(mut db) = Db::open_existing()? else {
+        println!("No runs recorded for \"{}\"", project_name);
+        return Ok(());
+    };
+    let Some(project) = db.get_project(project_name)? else {
+        println!("No runs recorded for \"{}\"", project_name);
+        return Ok(());
+    };
+    let runs = db.get_runs(&project, limit)?;
+    if runs.is_empty() {
+        println!("No runs recorded for \"{}\"", project_name);
+        return Ok(());
+    }
+    for run in runs {
+        println!(
+            "\n{} {} ({})",
+            format_timestamp(run.started_at).bold(),
+            format_status(&run.status, 0),
+            format_duration(run.started_at, run.finished_at)
+        );
+        if let Some(error) = &run.error {
+            println!("  {}", error.red());
+        }
+        for tool_run in db.get_tool_runs(&run)? {
+            println!(
+                "  step {} tool {} {:<8} {} {}",
+                tool_run.step + 1,
+                tool_run.tool + 1,
+                tool_run.tool_name,
+                format_status(&tool_run.status, 8),
+                format_duration(tool_run.started_at, tool_run.finished_at).dimmed()
+            );
+            if let Some(error) = &tool_run.error {
+                println!("    {}", error.red());
+            }
+        }
+    }
+    Ok(())
+}
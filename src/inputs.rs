@@ -0,0 +1,128 @@
+use owo_colors::OwoColorize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::db::Db;
+use crate::engine::ProgressStore;
+use crate::error::Result;
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum InputType {
+    #[default]
+    Text,
+    /// An email address
+    Email,
+    /// A whole or decimal number
+    Number,
+    /// A yes/no question, answered with `true` or `false`
+    Confirm,
+    /// One of `options`
+    Select,
+}
+
+/// A value asked from the user on the first run and remembered for the
+/// project, available as `{{ name }}` like `vars`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    /// Variable name of the answer
+    pub name: String,
+    /// Question shown to the user, defaults to the name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Kind of answer, used to validate it, defaults to `text`
+    #[serde(default, rename = "type")]
+    pub kind: InputType,
+    /// Answer suggested to the user and used in non-interactive mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Choices of a `select` input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    /// Accept an empty answer
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl Input {
+    pub fn get_prompt(&self) -> &str {
+        self.prompt.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Checks that `value` is a valid answer
+    pub fn validate(&self, value: &str) -> std::result::Result<(), String> {
+        if value.is_empty() {
+            return match self.optional {
+                true => Ok(()),
+                false => Err("An answer is required".to_string()),
+            };
+        }
+        match self.kind {
+            InputType::Text => Ok(()),
+            InputType::Email => match value.split_once('@') {
+                Some((user, domain)) if !user.is_empty() && domain.contains('.') => Ok(()),
+                _ => Err(format!("`{}` is not an email address", value)),
+            },
+            InputType::Number => match value.parse::<f64>() {
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!("`{}` is not a number", value)),
+            },
+            InputType::Confirm => match value {
+                "true" | "false" => Ok(()),
+                _ => Err(format!("`{}` is not `true` or `false`", value)),
+            },
+            InputType::Select => match &self.options {
+                Some(options) if options.iter().any(|o| o == value) => Ok(()),
+                _ => Err(format!("`{}` is not one of the options", value)),
+            },
+        }
+    }
+}
+
+/// Prints the stored answers to a project's inputs
+pub fn print_inputs(project_name: &str, inputs: &[Input]) -> Result<()> {
+    if inputs.is_empty() {
+        println!("\"{}\" has no inputs", project_name);
+        return Ok(());
+    }
+    let answers = match Db::open_existing()? {
+        Some(mut db) => match db.get_project(project_name)? {
+            Some(project) => db.get_project_inputs(&project)?,
+            None => Vec::new(),
+        },
+        None => Vec::new(),
+    };
+    for input in inputs {
+        let answer = answers.iter().find(|a| a.name == input.name);
+        match answer {
+            Some(answer) => println!("{}: {}", input.name.bold(), answer.value),
+            None => println!("{}: {}", input.name.bold(), "not answered yet".dimmed()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_answers() {
+        let email: Input = serde_yaml::from_str("name: git_email\ntype: email").unwrap();
+        assert!(email.validate("dev@example.com").is_ok());
+        assert!(email.validate("dev").is_err());
+        assert!(email.validate("").is_err());
+        let team: Input = serde_yaml::from_str(
+            "name: team\ntype: select\noptions: [web, mobile]\noptional: true",
+        )
+        .unwrap();
+        assert!(team.validate("web").is_ok());
+        assert!(team.validate("").is_ok());
+        assert!(team.validate("backend").is_err());
+        assert!(serde_yaml::from_str::<Input>("name: n\ntype: number")
+            .unwrap()
+            .validate("3.5")
+            .is_ok());
+    }
+}
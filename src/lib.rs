@@ -0,0 +1,30 @@
+//! Sets up machines from YAML project files. The `siu` binary is a thin
+//! client of this crate: parse a project with `projects::get` or
+//! `validation::parse`, then run it with an `Engine`, choosing where
+//! progress is stored, how commands are run and how the user is prompted.
+
+pub mod conditions;
+pub mod db;
+pub mod diff;
+pub mod engine;
+pub mod error;
+pub mod events;
+pub mod history;
+pub mod inputs;
+pub mod models;
+pub mod projects;
+pub mod prompt;
+pub mod run_log;
+pub mod schema;
+pub mod shell;
+pub mod tools;
+pub mod ui;
+pub mod validation;
+pub mod vars;
+
+pub use engine::{Engine, ProgressStore, RunOutcome};
+pub use error::{Error, Result};
+pub use events::{Event, Observer};
+pub use projects::{ProjectConfiguration, YamlConfiguration};
+pub use tools::runner::{CommandRunner, SystemRunner};
+pub use ui::{TerminalUi, Ui};
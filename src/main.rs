@@ -1,41 +1,208 @@
-use std::error::Error;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::exit;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use owo_colors::OwoColorize;
 
-pub mod db;
-pub mod diff;
-pub mod models;
-pub mod projects;
-pub mod schema;
-pub mod shell;
-pub mod tools;
+use siu::events::{EventFormat, JsonEvents};
+use siu::run_log::RunLog;
+use siu::{
+    db, error, history, inputs, projects, prompt, vars, Engine, Error, SystemRunner, TerminalUi,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    project: String,
+    #[command(subcommand)]
+    command: Option<Commands>,
+    #[arg(required = true)]
+    project: Option<String>,
     #[arg(short, long, help = "Resets a project's progress")]
     reset: bool,
+    #[arg(
+        long,
+        help = "Prints the commands a project would run without running them"
+    )]
+    dry_run: bool,
+    #[arg(
+        long,
+        env = "SIU_NON_INTERACTIVE",
+        help = "Never prompt, fail when a decision cannot be made automatically"
+    )]
+    non_interactive: bool,
+    #[arg(
+        short,
+        long,
+        env = "SIU_YES",
+        help = "Accept all confirmations, implies --non-interactive"
+    )]
+    yes: bool,
+    #[arg(
+        long,
+        value_enum,
+        env = "SIU_ON_PAUSE",
+        default_value_t = prompt::PausePolicy::Continue,
+        help = "What to do at pauses in non-interactive mode"
+    )]
+    on_pause: prompt::PausePolicy,
+    #[arg(long, value_enum, help = "Writes run events in this format")]
+    events: Option<EventFormat>,
+    #[arg(
+        long,
+        requires = "events",
+        help = "Writes events to this file instead of stdout"
+    )]
+    events_file: Option<PathBuf>,
+    #[arg(
+        long = "set",
+        value_name = "NAME=VALUE",
+        value_parser = vars::parse_assignment,
+        global = true,
+        help = "Overrides a variable of the project file"
+    )]
+    vars: Vec<(String, String)>,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut db = db::Db::default();
-    db.run_migrations();
-    projects::init();
-    let cli = Cli::parse();
-    let project = projects::get(&cli.project).unwrap();
-    if cli.reset {
-        if let Err(e) = project.reset() {
-            println!("\n{}", e.red());
-        }
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Lists all local projects and their progress
+    List,
+    /// Shows the progress of every step of a project
+    Status { project: String },
+    /// Checks that a project file is valid without running it
+    Validate { file: String },
+    /// Prints the JSON Schema of the project file format
+    Schema,
+    /// Shows the answers to a project's inputs
+    Inputs {
+        project: String,
+        #[arg(short, long, help = "Asks every input again")]
+        edit: bool,
+    },
+    /// Shows the latest runs of a project and the outcome of every tool
+    History {
+        project: String,
+        #[arg(short, long, default_value_t = 10, help = "Number of runs to show")]
+        limit: i64,
+    },
+}
+
+fn list_projects(overrides: &BTreeMap<String, String>) -> error::Result<()> {
+    let projects = projects::get_all()?;
+    if projects.is_empty() {
+        println!("No projects found");
         return Ok(());
     }
-    let title = format!("Will setup \"{}\"", project.options.name);
-    println!("\n\n{}", title.green().bold());
-    println!("{}\n\n", project.options.description.cyan());
-    if let Err(e) = project.setup() {
-        println!("\n{}", e.red());
+    for project in projects {
+        println!(
+            "\n{} ({})",
+            project.options.name.green().bold(),
+            project.get_status(overrides)?
+        );
+        println!("  {}", project.options.description.cyan());
+        println!("  {}", project.get_source().italic());
     }
     Ok(())
 }
+
+fn run(cli: Cli) -> error::Result<()> {
+    let overrides: BTreeMap<String, String> = cli.vars.iter().cloned().collect();
+    // Events on stdout must not be mixed with the regular output
+    let ui = TerminalUi {
+        quiet: cli.events.is_some() && cli.events_file.is_none(),
+        non_interactive: cli.non_interactive,
+        yes: cli.yes,
+        on_pause: cli.on_pause,
+    };
+    if let Some(Commands::List) = cli.command {
+        projects::init()?;
+        return list_projects(&overrides);
+    }
+    if let Some(Commands::Status { project }) = cli.command {
+        projects::init()?;
+        return projects::get(&project)?.print_status(&overrides, &ui);
+    }
+    if let Some(Commands::History { project, limit }) = cli.command {
+        projects::init()?;
+        let project = projects::get(&project)?;
+        return history::print_history(&project.options.name, limit);
+    }
+    if let Some(Commands::Inputs { project, edit }) = cli.command {
+        projects::init()?;
+        let project = projects::get(&project)?;
+        if edit {
+            let mut db = db::Db::new()?;
+            db.run_migrations()?;
+            Engine::new(&mut db, &SystemRunner, &ui).edit_inputs(&project)?;
+        }
+        return inputs::print_inputs(&project.options.name, &project.options.inputs);
+    }
+    if let Some(Commands::Schema) = cli.command {
+        println!("{}", projects::get_json_schema());
+        return Ok(());
+    }
+    if let Some(Commands::Validate { file }) = cli.command {
+        // Also reports undefined variables
+        let project = projects::get(&file)?.with_vars(&BTreeMap::new(), &overrides)?;
+        println!(
+            "{}",
+            format!(
+                "\"{}\" is valid ({} steps)",
+                project.options.name,
+                project.options.steps.len()
+            )
+            .green()
+        );
+        return Ok(());
+    }
+    projects::init()?;
+    // Required by clap whenever no subcommand is given
+    let project = projects::get(&cli.project.unwrap_or_default())?;
+    if cli.dry_run {
+        return project.print_plan(&overrides, &ui);
+    }
+    let events_writer: Option<Box<dyn Write>> = match (cli.events, &cli.events_file) {
+        (None, _) => None,
+        (Some(_), None) => Some(Box::new(io::stdout())),
+        (Some(_), Some(path)) => Some(Box::new(File::create(path).map_err(|e| {
+            Error::Io(format!(
+                "Failed to create events file {}: {}",
+                path.display(),
+                e
+            ))
+        })?)),
+    };
+    let events = events_writer.map(JsonEvents::new);
+    let mut db = db::Db::new()?;
+    db.run_migrations()?;
+    if cli.reset {
+        return Engine::new(&mut db, &SystemRunner, &ui).reset(&project);
+    }
+    let log = RunLog::create(&project.options.name)?;
+    let mut engine = Engine::new(&mut db, &SystemRunner, &ui).with_overrides(overrides);
+    engine.subscribe(&log);
+    if let Some(events) = &events {
+        engine.subscribe(events);
+    }
+    match engine.run(&project) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            if !matches!(e, Error::Aborted(_)) {
+                eprintln!("\nThe log of this run is at {}", log.path().display());
+            }
+            Err(e)
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("\n{}", e.to_string().red());
+        exit(e.exit_code());
+    }
+}
@@ -1,4 +1,6 @@
-use crate::schema::{projects, projects_progress};
+use crate::schema::{
+    completed_steps, project_inputs, projects, projects_progress, runs, tool_runs,
+};
 use diesel::prelude::*;
 
 #[derive(Queryable, Selectable, Identifiable, Clone)]
@@ -8,6 +10,10 @@ pub struct Project {
     pub id: i32,
     pub name: String,
     pub path: String,
+    /// Hash of the project file when the last run started
+    pub content_hash: Option<String>,
+    /// Project file contents when the last run started, used to show what changed
+    pub content: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -17,7 +23,7 @@ pub struct NewProject<'a> {
     pub path: &'a str,
 }
 
-#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Copy, Debug)]
+#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Debug)]
 #[diesel(table_name = projects_progress)]
 #[diesel(belongs_to(Project))]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -27,6 +33,10 @@ pub struct ProjectProgress {
     pub step: i32,
     pub tool: i32,
     pub tool_step: i32,
+    /// Identifies the step at `step`, so progress survives edits to the project
+    pub step_key: Option<String>,
+    /// Identifies the tool at `tool` within the step
+    pub tool_key: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -37,3 +47,101 @@ pub struct NewProjectProgress {
     pub tool: i32,
     pub tool_step: i32,
 }
+
+/// An answer to one of the project's `inputs`
+#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Debug)]
+#[diesel(table_name = project_inputs)]
+#[diesel(belongs_to(Project))]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProjectInput {
+    pub id: i32,
+    pub project_id: i32,
+    pub name: String,
+    pub value: String,
+    /// Unix timestamp in milliseconds
+    pub updated_at: i64,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = project_inputs)]
+pub struct NewProjectInput<'a> {
+    pub project_id: i32,
+    pub name: &'a str,
+    pub value: &'a str,
+    pub updated_at: i64,
+}
+
+/// A step that ran to the end, or was skipped, since the project was last
+/// started from the beginning
+#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Debug)]
+#[diesel(table_name = completed_steps)]
+#[diesel(belongs_to(Project))]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CompletedStep {
+    pub id: i32,
+    pub project_id: i32,
+    /// Key of the step, see `StepConfiguration::get_key`
+    pub step_key: String,
+    /// Unix timestamp in milliseconds
+    pub completed_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = completed_steps)]
+pub struct NewCompletedStep<'a> {
+    pub project_id: i32,
+    pub step_key: &'a str,
+    pub completed_at: i64,
+}
+
+#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Debug)]
+#[diesel(table_name = runs)]
+#[diesel(belongs_to(Project))]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProjectRun {
+    pub id: i32,
+    pub project_id: i32,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = runs)]
+pub struct NewProjectRun<'a> {
+    pub project_id: i32,
+    pub started_at: i64,
+    pub status: &'a str,
+}
+
+#[derive(Queryable, Selectable, Associations, Identifiable, Clone, Debug)]
+#[diesel(table_name = tool_runs)]
+#[diesel(belongs_to(ProjectRun, foreign_key = run_id))]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ToolRun {
+    pub id: i32,
+    pub run_id: i32,
+    pub step: i32,
+    pub tool: i32,
+    pub tool_step: i32,
+    pub tool_name: String,
+    /// Unix timestamp in milliseconds
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tool_runs)]
+pub struct NewToolRun<'a> {
+    pub run_id: i32,
+    pub step: i32,
+    pub tool: i32,
+    pub tool_step: i32,
+    pub tool_name: &'a str,
+    pub started_at: i64,
+    pub status: &'a str,
+}
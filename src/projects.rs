@@ -1,236 +1,1048 @@
-use dialoguer::theme::ColorfulTheme;
-use dialoguer::Confirm;
 use owo_colors::OwoColorize;
 use url::Url;
 
-use std::process::exit;
-use std::{env, fs, path::Path};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{env, fmt, fs, path::Path, thread};
 
-use serde::{Deserialize, Serialize};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{Metadata, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
 use walkdir::DirEntry;
 use walkdir::WalkDir;
 
+use crate::conditions::Condition;
 use crate::db::Db;
-use crate::models::Project;
-use crate::models::ProjectProgress;
+use crate::engine::ProgressStore;
+use crate::error::{self, Error};
+use crate::inputs::Input;
+use crate::models::{Project, ProjectProgress};
 use crate::tools::chezmoi::Chezmoi;
 use crate::tools::homebrew::Homebrew;
-use crate::tools::java11::Java11;
+use crate::tools::jdk::Jdk;
+use crate::tools::node::Node;
+use crate::tools::package::Package;
+use crate::tools::package_manager::PackageManager;
 use crate::tools::pnpm::Pnpm;
 use crate::tools::rbenv::Rbenv;
-use crate::tools::types::Tool;
+use crate::tools::run::Run;
+use crate::tools::runner::{CommandRunner, SystemRunner};
+use crate::tools::system_packages::SystemPackages;
+use crate::tools::types::{Context, PlannedAction, Tool};
 use crate::tools::yarn::Yarn;
+use crate::ui::Ui;
+use crate::validation;
+use crate::vars;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
+#[schemars(deny_unknown_fields)]
 pub enum RunTool {
+    /// Installs packages with apt, on Debian and Ubuntu
+    Apt { apt: SystemPackages },
+    /// Installs chezmoi and initialises your dotfiles
     Chezmoi { chezmoi: Chezmoi },
+    /// Installs packages with dnf, on Fedora and RHEL
+    Dnf { dnf: SystemPackages },
+    /// Installs homebrew packages
     Homebrew { brew: Homebrew },
+    /// Installs the default JDK 11 and sets JAVA_HOME, same as `jdk: 11`
     Java11 { java11: bool },
+    /// Installs a JDK and sets JAVA_HOME
+    Jdk { jdk: Jdk },
+    /// Installs node versions with a version manager and enables corepack
+    Node { node: Node },
+    /// Prints a note
     Note { note: String },
+    /// Installs packages with the package manager of the machine, mapping
+    /// names that differ between package managers
+    Package { package: Package },
+    /// Installs packages with pacman, on Arch Linux
+    Pacman { pacman: SystemPackages },
+    /// Asks for confirmation before continuing, allowing to finish later
     Pause { pause: bool },
+    /// Installs global pnpm packages
     Pnpm { pnpm: Pnpm },
+    /// Installs a ruby version with rbenv
     Rbenv { rbenv: Rbenv },
+    /// Runs an arbitrary command
+    Run { run: Run },
+    /// Installs global yarn packages
     Yarn { yarn: Yarn },
 }
 
+pub const TOOL_NAMES: &[&str] = &[
+    "apt", "brew", "chezmoi", "dnf", "java11", "jdk", "node", "note", "package", "pacman", "pause",
+    "pnpm", "rbenv", "run", "yarn",
+];
+
+/// A single item of a step's `run` list: a tool plus its optional settings.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+pub struct ToolConfiguration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+    #[serde(flatten)]
+    pub tool: RunTool,
+}
+
+impl ToolConfiguration {
+    /// Identifies the tool within its step, used to resume progress
+    pub fn get_key(&self) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None => get_content_hash(&serde_yaml::to_string(&self.tool).unwrap()),
+        }
+    }
+}
+
+struct ToolConfigurationVisitor;
+
+impl<'de> Visitor<'de> for ToolConfigurationVisitor {
+    type Value = ToolConfiguration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tool, e.g. `brew: git`")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<ToolConfiguration, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut id: Option<String> = None;
+        let mut when: Option<Condition> = None;
+        let mut tool: Option<(String, RunTool)> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "id" {
+                id = Some(map.next_value()?);
+                continue;
+            }
+            if key == "when" {
+                when = Some(map.next_value()?);
+                continue;
+            }
+            if let Some((name, _)) = &tool {
+                return Err(de::Error::custom(format!(
+                    "unexpected key `{}` next to `{}`, every list item must contain a single tool",
+                    key, name
+                )));
+            }
+            let value = RunTool::next_value(&key, &mut map)?;
+            tool = Some((key, value));
+        }
+        match tool {
+            Some((_, tool)) => Ok(ToolConfiguration { id, when, tool }),
+            None => Err(de::Error::custom("expected a tool, e.g. `brew: git`")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ToolConfiguration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ToolConfigurationVisitor)
+    }
+}
+
+impl JsonSchema for ToolConfiguration {
+    fn schema_name() -> String {
+        "ToolConfiguration".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = RunTool::json_schema(gen).into_object();
+        let id = gen.subschema_for::<Option<String>>().into_object();
+        let id = Schema::Object(SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "Stable identifier used to resume progress, defaults to a hash of the tool"
+                        .to_string(),
+                ),
+                ..Default::default()
+            })),
+            ..id
+        });
+        let when = gen.subschema_for::<Option<Condition>>();
+        for variant in schema.subschemas().any_of.iter_mut().flatten() {
+            if let Schema::Object(variant) = variant {
+                let properties = &mut variant.object().properties;
+                properties.insert("id".to_string(), id.clone());
+                properties.insert("when".to_string(), when.clone());
+            }
+        }
+        Schema::Object(schema)
+    }
+}
+
 impl RunTool {
-    pub fn install(self, tool_step: usize) -> Result<bool, String> {
+    /// Reads the value of the tool called `name` from a `run` list item
+    fn next_value<'de, A>(name: &str, map: &mut A) -> Result<RunTool, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let tool = match name {
+            "apt" => RunTool::Apt {
+                apt: map.next_value()?,
+            },
+            "brew" => RunTool::Homebrew {
+                brew: map.next_value()?,
+            },
+            "chezmoi" => RunTool::Chezmoi {
+                chezmoi: map.next_value()?,
+            },
+            "dnf" => RunTool::Dnf {
+                dnf: map.next_value()?,
+            },
+            "java11" => {
+                let java11: bool = map.next_value()?;
+                if !java11 {
+                    return Err(de::Error::custom(
+                        "`java11: false` installs nothing, remove the item instead",
+                    ));
+                }
+                RunTool::Java11 { java11 }
+            }
+            "jdk" => RunTool::Jdk {
+                jdk: map.next_value()?,
+            },
+            "node" => RunTool::Node {
+                node: map.next_value()?,
+            },
+            "note" => RunTool::Note {
+                note: map.next_value()?,
+            },
+            "package" => RunTool::Package {
+                package: map.next_value()?,
+            },
+            "pacman" => RunTool::Pacman {
+                pacman: map.next_value()?,
+            },
+            "pause" => RunTool::Pause {
+                pause: map.next_value()?,
+            },
+            "pnpm" => RunTool::Pnpm {
+                pnpm: map.next_value()?,
+            },
+            "rbenv" => RunTool::Rbenv {
+                rbenv: map.next_value()?,
+            },
+            "run" => RunTool::Run {
+                run: map.next_value()?,
+            },
+            "yarn" => RunTool::Yarn {
+                yarn: map.next_value()?,
+            },
+            unknown => return Err(de::Error::custom(validation::unknown_tool_message(unknown))),
+        };
+        Ok(tool)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            RunTool::Apt { apt: _ } => "apt",
+            RunTool::Chezmoi { chezmoi: _ } => "chezmoi",
+            RunTool::Dnf { dnf: _ } => "dnf",
+            RunTool::Homebrew { brew: _ } => "brew",
+            RunTool::Java11 { java11: _ } => "java11",
+            RunTool::Jdk { jdk: _ } => "jdk",
+            RunTool::Node { node: _ } => "node",
+            RunTool::Note { note: _ } => "note",
+            RunTool::Package { package: _ } => "package",
+            RunTool::Pacman { pacman: _ } => "pacman",
+            RunTool::Pause { pause: _ } => "pause",
+            RunTool::Pnpm { pnpm: _ } => "pnpm",
+            RunTool::Rbenv { rbenv: _ } => "rbenv",
+            RunTool::Run { run: _ } => "run",
+            RunTool::Yarn { yarn: _ } => "yarn",
+        }
+    }
+
+    pub fn plan(&self, tool_step: usize) -> Vec<PlannedAction> {
+        match self {
+            RunTool::Apt { apt } => apt.with(PackageManager::Apt).plan(tool_step),
+            RunTool::Chezmoi { chezmoi } => chezmoi.plan(tool_step),
+            RunTool::Dnf { dnf } => dnf.with(PackageManager::Dnf).plan(tool_step),
+            RunTool::Homebrew { brew } => brew.plan(tool_step),
+            RunTool::Java11 { java11: _ } => Jdk::java11().plan(tool_step),
+            RunTool::Jdk { jdk } => jdk.plan(tool_step),
+            RunTool::Node { node } => node.plan(tool_step),
+            RunTool::Note { note } => vec![PlannedAction::Note(note.clone())],
+            RunTool::Package { package } => package.plan(tool_step),
+            RunTool::Pacman { pacman } => pacman.with(PackageManager::Pacman).plan(tool_step),
+            RunTool::Pause { pause: _ } => vec![PlannedAction::Pause(
+                "Wait for confirmation before continuing".to_string(),
+            )],
+            RunTool::Pnpm { pnpm } => pnpm.plan(tool_step),
+            RunTool::Rbenv { rbenv } => rbenv.plan(tool_step),
+            RunTool::Run { run } => run.plan(tool_step),
+            RunTool::Yarn { yarn } => yarn.plan(tool_step),
+        }
+    }
+
+    pub fn check(&self, runner: &dyn CommandRunner) -> bool {
         match self {
-            RunTool::Chezmoi { chezmoi } => chezmoi.install(tool_step),
-            RunTool::Homebrew { brew } => brew.install(tool_step),
-            RunTool::Java11 { java11: _ } => Java11 {}.install(tool_step),
+            RunTool::Apt { apt } => apt.with(PackageManager::Apt).check(runner),
+            RunTool::Chezmoi { chezmoi } => chezmoi.check(runner),
+            RunTool::Dnf { dnf } => dnf.with(PackageManager::Dnf).check(runner),
+            RunTool::Homebrew { brew } => brew.check(runner),
+            RunTool::Java11 { java11: _ } => Jdk::java11().check(runner),
+            RunTool::Jdk { jdk } => jdk.check(runner),
+            RunTool::Node { node } => node.check(runner),
+            RunTool::Note { note: _ } => false,
+            RunTool::Package { package } => package.check(runner),
+            RunTool::Pacman { pacman } => pacman.with(PackageManager::Pacman).check(runner),
+            RunTool::Pause { pause: _ } => false,
+            RunTool::Pnpm { pnpm } => pnpm.check(runner),
+            RunTool::Rbenv { rbenv } => rbenv.check(runner),
+            RunTool::Run { run } => run.check(runner),
+            RunTool::Yarn { yarn } => yarn.check(runner),
+        }
+    }
+
+    pub fn install(self, ctx: &Context, tool_step: usize) -> error::Result<bool> {
+        match self {
+            RunTool::Apt { apt } => apt.with(PackageManager::Apt).install(ctx, tool_step),
+            RunTool::Chezmoi { chezmoi } => chezmoi.install(ctx, tool_step),
+            RunTool::Dnf { dnf } => dnf.with(PackageManager::Dnf).install(ctx, tool_step),
+            RunTool::Homebrew { brew } => brew.install(ctx, tool_step),
+            RunTool::Java11 { java11: _ } => Jdk::java11().install(ctx, tool_step),
+            RunTool::Jdk { jdk } => jdk.install(ctx, tool_step),
+            RunTool::Node { node } => node.install(ctx, tool_step),
             RunTool::Note { note } => {
-                println!("\n\n{}\n", note.bold());
+                ctx.ui.note(&note);
                 Ok(false)
             }
-            RunTool::Pause { pause: _ } => {
-                let theme = ColorfulTheme::default();
-                println!("\nAre you ready to continue?");
-                match Confirm::with_theme(&theme)
-                    .with_prompt(
-                        "Press Enter or 'y' to continue or Esc, 'q' or 'n' to exit and finish later",
-                    )
-                    .default(true)
-                    .interact_opt()
-                    .unwrap()
-                {
-                    Some(true) => Ok(false),
-                    Some(false) => exit(0),
-                    None => exit(0),
-                }
+            RunTool::Package { package } => package.install(ctx, tool_step),
+            RunTool::Pacman { pacman } => {
+                pacman.with(PackageManager::Pacman).install(ctx, tool_step)
             }
-            RunTool::Pnpm { pnpm } => pnpm.install(tool_step),
-            RunTool::Rbenv { rbenv } => rbenv.install(tool_step),
-            RunTool::Yarn { yarn } => yarn.install(tool_step),
+            RunTool::Pause { pause: _ } => match ctx.ui.pause()? {
+                true => Ok(false),
+                false => Err(Error::Aborted(
+                    "Stopped at a pause, run siu again to continue".to_string(),
+                )),
+            },
+            RunTool::Pnpm { pnpm } => pnpm.install(ctx, tool_step),
+            RunTool::Rbenv { rbenv } => rbenv.install(ctx, tool_step),
+            RunTool::Run { run } => run.install(ctx, tool_step),
+            RunTool::Yarn { yarn } => yarn.install(ctx, tool_step),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct StepConfiguration {
+    /// Stable identifier used to resume progress, defaults to a hash of the description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Shown as the step title while running
     pub description: String,
-    pub run: Vec<RunTool>,
+    /// Only run the step on machines matching this condition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<Condition>,
+    /// File the step was included from, None for the project's own steps
+    #[serde(skip)]
+    pub file: Option<String>,
+    /// Tools run in order, each item contains a single tool
+    pub run: Vec<ToolConfiguration>,
+}
+
+impl StepConfiguration {
+    /// Names the file an included step came from, for step titles
+    pub fn get_attribution(&self) -> String {
+        match &self.file {
+            Some(file) => format!(" (from {})", file),
+            None => String::new(),
+        }
+    }
+
+    /// Identifies the step within the project, used to resume progress
+    pub fn get_key(&self) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None => get_content_hash(&self.description),
+        }
+    }
+}
+
+/// Key of a top-level entry siu ignores, it has to start with `x-`, e.g.
+/// `x-defaults` holding YAML anchors the steps refer to
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub struct ExtensionKey(String);
+
+impl<'de> Deserialize<'de> for ExtensionKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let key = String::deserialize(deserializer)?;
+        match key.starts_with("x-") {
+            true => Ok(ExtensionKey(key)),
+            false => Err(de::Error::custom(format!(
+                "unknown field `{}`, expected one of `name`, `description`, `include`, `vars`, `inputs`, `steps` or a key starting with `x-`",
+                key
+            ))),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+/// A siu project file
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
+#[schemars(deny_unknown_fields)]
 pub struct YamlConfiguration {
+    /// Unique name, used to store the project's progress
     pub name: String,
     pub description: String,
+    /// Projects whose steps run before this project's own: paths relative to
+    /// this file, names of projects in the projects dir or urls
+    #[serde(default, alias = "extends", skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Values for `{{ name }}` placeholders in the project's strings, can be
+    /// overridden with `--set name=value` or `SIU_VAR_<name>`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    /// Values asked on the first run and remembered, used like `vars`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Input>,
     pub steps: Vec<StepConfiguration>,
+    /// Keys starting with `x-`, siu ignores them
+    #[serde(flatten, skip_serializing)]
+    #[schemars(skip)]
+    pub extensions: BTreeMap<ExtensionKey, serde_yaml::Value>,
+}
+
+pub enum ProgressStatus {
+    NotStarted,
+    InProgress { step: usize, total: usize },
+    Completed,
+}
+
+impl fmt::Display for ProgressStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressStatus::NotStarted => write!(f, "not started"),
+            ProgressStatus::InProgress { step, total } => {
+                write!(f, "step {} of {}", step + 1, total)
+            }
+            ProgressStatus::Completed => write!(f, "completed"),
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct ProjectConfiguration {
     pub options: YamlConfiguration,
+    /// Raw project file, as read from disk or fetched from the url, followed
+    /// by the files it includes
+    pub source: String,
     // Actual path in the system
     pub path: Option<String>,
     pub url: Option<String>,
 }
 
+/// Position to resume a project at: step, tool and tool step indices.
+pub type Position = (usize, usize, usize);
+
+/// What the database knows about a project's progress
+#[derive(Default)]
+struct SavedProgress {
+    progress: Option<ProjectProgress>,
+    /// Keys of the steps completed since the project was started
+    completed: Vec<String>,
+}
+
 impl ProjectConfiguration {
-    fn run_step(
+    /// Returns where a run should start from the saved progress. Completed
+    /// projects start over from the beginning.
+    ///
+    /// The saved step and tool keys are used to find the position even if
+    /// steps or tools were added, removed or reordered since the progress was
+    /// saved. If they cannot be found anymore, the saved indices are returned
+    /// as an error, clamped to the current project file.
+    pub fn get_start_position(
         &self,
-        project: &Project,
-        db: &mut Db,
-        index: usize,
-        tool: usize,
-        tool_step: usize,
-    ) -> Result<ProjectProgress, String> {
-        let step = &self.options.steps[index];
-        println!("\n{}", step.description.underline().bold());
-
-        let tools: Vec<RunTool> = step.run.clone().drain(tool..).collect();
-
-        for run in tools {
-            match run.install(tool_step) {
-                Ok(pause) => {
-                    if pause {
-                        let saved_progress = db.update_project_progress(
-                            project,
-                            &(index as i32),
-                            &(tool as i32),
-                            &(tool_step as i32 + 1),
-                        );
-                        return Ok(saved_progress);
-                    }
+        progress: Option<&ProjectProgress>,
+    ) -> Result<Position, Position> {
+        let steps = &self.options.steps;
+        let Some(p) = progress else {
+            return Ok((0, 0, 0));
+        };
+        let (step, tool, tool_step) = (p.step as usize, p.tool as usize, p.tool_step as usize);
+        // Completed projects and progress saved before keys were stored
+        let Some(step_key) = &p.step_key else {
+            return match steps.get(step) {
+                Some(step_config) => Ok((step, tool.min(step_config.run.len()), tool_step)),
+                None => Ok((0, 0, 0)),
+            };
+        };
+        let step = match steps.get(step) {
+            Some(step_config) if &step_config.get_key() == step_key => step,
+            _ => match steps.iter().position(|s| &s.get_key() == step_key) {
+                Some(index) => index,
+                None => {
+                    let step = step.min(steps.len().saturating_sub(1));
+                    let tools = steps.get(step).map_or(0, |s| s.run.len());
+                    return Err((step, tool.min(tools), 0));
                 }
-                Err(e) => return Err(e),
+            },
+        };
+        let run = &steps[step].run;
+        let Some(tool_key) = &p.tool_key else {
+            return Ok((step, tool.min(run.len()), tool_step));
+        };
+        if run.get(tool).map(|t| t.get_key()).as_ref() == Some(tool_key) {
+            return Ok((step, tool, tool_step));
+        }
+        match run.iter().position(|t| &t.get_key() == tool_key) {
+            Some(index) => Ok((step, index, tool_step)),
+            None => Err((step, tool.min(run.len()), 0)),
+        }
+    }
+
+    /// Whether the step at `index` ran already, given the resume `position`
+    /// and the keys of the steps `completed` since the project was started.
+    /// Without any record, e.g. for progress saved by an older version, the
+    /// steps before the position count as done.
+    pub fn is_step_done(&self, index: usize, position: Position, completed: &[String]) -> bool {
+        match completed {
+            [] => index < position.0,
+            _ => completed.contains(&self.options.steps[index].get_key()),
+        }
+    }
+
+    /// Steps before the resume `position` that never ran, because they were
+    /// added to the project file after the run went past them
+    pub fn get_new_steps(&self, position: Position, completed: &[String]) -> Vec<usize> {
+        (0..position.0.min(self.options.steps.len()))
+            .filter(|index| !self.is_step_done(*index, position, completed))
+            .collect()
+    }
+
+    /// `overrides` are variables set with `--set`
+    pub fn get_status(
+        &self,
+        overrides: &BTreeMap<String, String>,
+    ) -> error::Result<ProgressStatus> {
+        let (config, saved) = self.get_saved_state(overrides)?;
+        Ok(config.get_progress_status(saved.progress.as_ref()))
+    }
+
+    fn get_progress_status(&self, progress: Option<&ProjectProgress>) -> ProgressStatus {
+        match progress {
+            Some(p) if p.step_key.is_none() && p.step as usize >= self.options.steps.len() => {
+                ProgressStatus::Completed
             }
-            db.update_project_progress(project, &(index as i32), &(tool as i32 + 1), &0);
-        }
-        let new_progress = db.update_project_progress(project, &((index + 1) as i32), &0, &0);
-        if index < self.options.steps.len() - 1 {
-            return self.run_step(
-                project,
-                db,
-                new_progress.step as usize,
-                new_progress.tool as usize,
-                new_progress.tool_step as usize,
-            );
+            _ => match self.get_start_position(progress) {
+                Ok((0, 0, 0)) | Err((0, 0, 0)) => ProgressStatus::NotStarted,
+                Ok((step, _, _)) | Err((step, _, _)) => ProgressStatus::InProgress {
+                    step,
+                    total: self.options.steps.len(),
+                },
+            },
         }
-        Ok(new_progress)
     }
 
-    fn get_project_progress(&self, db: &mut Db) -> (Project, ProjectProgress) {
-        match db.get_project(&self.options.name) {
-            Some(project) => (project.clone(), db.get_project_progress(&project)),
-            None => {
-                let path = if let Some(p) = &self.path {
-                    p
-                } else if let Some(u) = &self.url {
-                    u
-                } else {
-                    panic!("Projects must have either a path or a url");
-                };
-                let project = db.create_project(&self.options.name, path);
-                (project.clone(), db.get_project_progress(&project))
+    pub fn get_source(&self) -> &str {
+        match (&self.path, &self.url) {
+            (Some(path), _) => path,
+            (None, Some(url)) => url,
+            (None, None) => "",
+        }
+    }
+
+    /// Reads the saved progress and fills in the stored input answers, like
+    /// a run does, so the step and tool keys match the saved ones. Never
+    /// creates the database or the project entry, so planning has no side
+    /// effects.
+    fn get_saved_state(
+        &self,
+        overrides: &BTreeMap<String, String>,
+    ) -> error::Result<(ProjectConfiguration, SavedProgress)> {
+        let Some(mut db) = Db::open_existing()? else {
+            return Ok((
+                self.with_vars(&BTreeMap::new(), overrides)?,
+                SavedProgress::default(),
+            ));
+        };
+        let Some(project) = db.get_project(&self.options.name)? else {
+            return Ok((
+                self.with_vars(&BTreeMap::new(), overrides)?,
+                SavedProgress::default(),
+            ));
+        };
+        let saved = SavedProgress {
+            progress: db.find_project_progress(&project)?,
+            completed: db
+                .get_completed_steps(&project)?
+                .into_iter()
+                .map(|c| c.step_key)
+                .collect(),
+        };
+        let answers: BTreeMap<String, String> = db
+            .get_project_inputs(&project)?
+            .into_iter()
+            .map(|input| (input.name, input.value))
+            .collect();
+        Ok((self.with_vars(&answers, overrides)?, saved))
+    }
+
+    /// Fills the variables, the `answers` to the inputs and the `overrides`
+    /// set with `--set` into the project. Loaded projects keep their
+    /// placeholders, this is done once before running or showing them.
+    pub fn with_vars(
+        &self,
+        answers: &BTreeMap<String, String>,
+        overrides: &BTreeMap<String, String>,
+    ) -> error::Result<Self> {
+        let options =
+            vars::apply(self.options.clone(), answers, overrides).map_err(|(step, message)| {
+                Error::Parse(match step {
+                    Some(index) => format!(
+                        "{}\n  in step {} \"{}\"",
+                        message,
+                        index + 1,
+                        self.options.steps[index].description
+                    ),
+                    None => message,
+                })
+            })?;
+        Ok(ProjectConfiguration {
+            options,
+            ..self.clone()
+        })
+    }
+
+    /// Whether the project file differs from the one used in the last run
+    fn has_changed(&self) -> error::Result<bool> {
+        let Some(mut db) = Db::open_existing()? else {
+            return Ok(false);
+        };
+        Ok(match db.get_project(&self.options.name)? {
+            Some(Project {
+                content_hash: Some(content_hash),
+                ..
+            }) => content_hash != get_content_hash(&self.source),
+            _ => false,
+        })
+    }
+
+    /// Like `get_start_position`, but warns instead of asking when the saved
+    /// position could not be found.
+    fn get_saved_start_position(
+        &self,
+        progress: Option<&ProjectProgress>,
+        ui: &dyn Ui,
+    ) -> Position {
+        match self.get_start_position(progress) {
+            Ok(position) => position,
+            Err(saved) => {
+                ui.warning(
+                    "The project file changed since the progress was saved, the position below is a guess",
+                );
+                saved
             }
         }
     }
 
-    pub fn setup(&self) -> Result<(), String> {
-        let mut db = Db::default();
-        let (project, progress) = self.get_project_progress(&mut db);
-        if progress.tool > 0 || progress.tool_step > 0 {
-            println!("{}", "Picking up where you left off".green().bold());
+    pub fn print_plan(
+        &self,
+        overrides: &BTreeMap<String, String>,
+        ui: &dyn Ui,
+    ) -> error::Result<()> {
+        let (config, saved) = self.get_saved_state(overrides)?;
+        config.print_plan_from(saved.progress.as_ref(), &saved.completed, ui);
+        Ok(())
+    }
+
+    fn print_plan_from(
+        &self,
+        progress: Option<&ProjectProgress>,
+        completed: &[String],
+        ui: &dyn Ui,
+    ) {
+        println!("Plan for \"{}\"", self.options.name);
+        let position = self.get_saved_start_position(progress, ui);
+        let (step, tool, tool_step) = position;
+        if step > 0 || tool > 0 || tool_step > 0 {
+            println!("Resuming at step {}, tool {}", step + 1, tool + 1);
         }
-        let progress_result = self.run_step(
-            &project,
-            &mut db,
-            progress.step as usize,
-            progress.tool as usize,
-            progress.tool_step as usize,
-        );
-        match progress_result {
-            Ok(progress) => {
-                if progress.step as usize > self.options.steps.len() - 1 {
-                    db.update_project_progress(&project, &0, &0, &0);
+        let new_steps = self.get_new_steps(position, completed);
+        if !new_steps.is_empty() {
+            let steps: Vec<String> = new_steps.iter().map(|i| (i + 1).to_string()).collect();
+            println!(
+                "Running the steps added before it first: {}",
+                steps.join(", ")
+            );
+        }
+        for (index, step_config) in self.options.steps.iter().enumerate() {
+            if self.is_step_done(index, position, completed) {
+                continue;
+            }
+            println!(
+                "\nStep {}: {}{}",
+                index + 1,
+                step_config.description,
+                step_config.get_attribution()
+            );
+            if let Some(when) = step_config
+                .when
+                .as_ref()
+                .filter(|w| !w.matches(&SystemRunner))
+            {
+                println!("  Skipped, only runs when {}", when);
+                continue;
+            }
+            let first_tool = if index == step { tool } else { 0 };
+            for (tool_index, run) in step_config.run.iter().enumerate().skip(first_tool) {
+                let sub_step = if index == step && tool_index == tool {
+                    tool_step
+                } else {
+                    0
+                };
+                println!("  {}. {}", tool_index + 1, run.tool.name());
+                if let Some(when) = run.when.as_ref().filter(|w| !w.matches(&SystemRunner)) {
+                    println!("     Skipped, only runs when {}", when);
+                    continue;
+                }
+                for action in run.tool.plan(sub_step) {
+                    println!("     {}", action);
                 }
-                Ok(())
             }
-            Err(e) => Err(e),
         }
     }
 
-    pub fn reset(&self) -> Result<(), String> {
-        let mut db = Db::default();
-        let project = db.get_project(&self.options.name).unwrap();
-        db.update_project_progress(&project, &0, &0, &0);
+    pub fn print_status(
+        &self,
+        overrides: &BTreeMap<String, String>,
+        ui: &dyn Ui,
+    ) -> error::Result<()> {
+        let changed = self.has_changed()?;
+        let (config, saved) = self.get_saved_state(overrides)?;
+        config.print_status_from(saved.progress.as_ref(), &saved.completed, changed, ui);
         Ok(())
     }
+
+    fn print_status_from(
+        &self,
+        progress: Option<&ProjectProgress>,
+        completed: &[String],
+        changed: bool,
+        ui: &dyn Ui,
+    ) {
+        let status = self.get_progress_status(progress);
+        println!(
+            "\n{} ({})",
+            self.options.name.green().bold(),
+            status.to_string().bold()
+        );
+        // Completed projects have no saved step
+        let saved_step = progress.and_then(|p| Some((p, self.options.steps.get(p.step as usize)?)));
+        if let Some((p, step)) = saved_step {
+            let mut saved = format!(
+                "Saved progress: step {} \"{}\"",
+                p.step + 1,
+                step.description
+            );
+            if let Some(tool) = step.run.get(p.tool as usize) {
+                saved += &format!(", tool {} ({})", p.tool + 1, tool.tool.name());
+            }
+            if p.tool_step > 0 {
+                saved += &format!(", {} sub-steps done", p.tool_step);
+            }
+            println!("{}", saved.italic());
+        }
+        if changed {
+            ui.warning("The project file changed since the last run");
+        }
+        let position = match status {
+            ProgressStatus::Completed => (self.options.steps.len(), 0, 0),
+            _ => self.get_saved_start_position(progress, ui),
+        };
+        let (step, tool, tool_step) = position;
+        for (index, step_config) in self.options.steps.iter().enumerate() {
+            let title = format!(
+                "Step {}: {}{}",
+                index + 1,
+                step_config.description,
+                step_config.get_attribution()
+            );
+            let step_when = step_config
+                .when
+                .as_ref()
+                .filter(|w| !w.matches(&SystemRunner));
+            if let Some(when) = step_when {
+                println!(
+                    "\n{} {} {}",
+                    "-".dimmed(),
+                    title.dimmed(),
+                    format!("(skipped, only runs when {})", when).italic()
+                );
+                continue;
+            }
+            let step_position = match self.is_step_done(index, position, completed) {
+                true => Ordering::Less,
+                false if index < step => Ordering::Greater,
+                false => index.cmp(&step),
+            };
+            match step_position {
+                Ordering::Less => println!("\n{} {}", "✔".green(), title),
+                Ordering::Equal => println!("\n{} {}", "▶".yellow(), title.bold()),
+                Ordering::Greater if index < step => println!(
+                    "\n{} {} {}",
+                    "·".dimmed(),
+                    title.dimmed(),
+                    "(added since, runs first)".italic()
+                ),
+                Ordering::Greater => println!("\n{} {}", "·".dimmed(), title.dimmed()),
+            }
+            for (tool_index, run) in step_config.run.iter().enumerate() {
+                let name = run.tool.name();
+                if let Some(when) = run.when.as_ref().filter(|w| !w.matches(&SystemRunner)) {
+                    println!(
+                        "    {} {} {}",
+                        "-".dimmed(),
+                        name.dimmed(),
+                        format!("(skipped, only runs when {})", when).italic()
+                    );
+                    continue;
+                }
+                let tool_position = match step_position {
+                    Ordering::Equal => tool_index.cmp(&tool),
+                    _ => step_position,
+                };
+                match tool_position {
+                    Ordering::Less => println!("    {} {}", "✔".green(), name),
+                    Ordering::Equal if tool_step > 0 => println!(
+                        "    {} {} {}",
+                        "▶".yellow(),
+                        name.bold(),
+                        format!("(sub-step {} done)", tool_step).italic()
+                    ),
+                    Ordering::Equal => println!("    {} {}", "▶".yellow(), name.bold()),
+                    Ordering::Greater => println!("    {} {}", "·".dimmed(), name.dimmed()),
+                }
+            }
+        }
+    }
+}
+pub fn get_content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
-fn get_projects_path() -> String {
+fn get_projects_path() -> error::Result<String> {
+    if let Ok(path) = env::var("SIU_PROJECTS_PATH") {
+        return Ok(path);
+    }
     match home::home_dir() {
-        Some(path) => env::var("SIU_PROJECTS_PATH")
-            .unwrap_or_else(|_| path.to_string_lossy().to_string() + "/.siu/projects"),
-        None => panic!("Home directory not found, please set the SIU_PROJECTS_PATH env variable"),
+        Some(path) => Ok(path.to_string_lossy().to_string() + "/.siu/projects"),
+        None => Err(Error::Project(
+            "Home directory not found, please set the SIU_PROJECTS_PATH env variable".to_string(),
+        )),
     }
 }
 
-pub fn init() {
-    let projects_path = get_projects_path();
+pub fn init() -> error::Result<()> {
+    let projects_path = get_projects_path()?;
     let projects_path_exists = Path::new(&projects_path).is_dir();
     if !projects_path_exists {
-        match fs::create_dir_all(projects_path) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("Failed to create projects dir: {}", e);
-            }
-        }
+        fs::create_dir_all(projects_path)
+            .map_err(|e| Error::Io(format!("Failed to create projects dir: {}", e)))?;
     }
+    Ok(())
 }
 
-fn parse_project_file(
-    project_file: &fs::File,
-    file_path: &Path,
-) -> Result<ProjectConfiguration, String> {
-    match serde_yaml::from_reader(project_file) {
-        Ok(options) => Ok(ProjectConfiguration {
-            options,
-            path: Some(file_path.to_str().unwrap().to_string()),
-            url: None,
-        }),
-        Err(e) => Err(format!("Failed to parse project file: {}", e)),
+/// Downloads a project file, for embedders running their own async runtime
+pub async fn fetch(url: &str) -> error::Result<String> {
+    let fetch_error =
+        |e: reqwest::Error| Error::Project(format!("Failed to fetch project from {}: {}", url, e));
+    let res = reqwest::get(url)
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(fetch_error)?;
+    res.text().await.map_err(fetch_error)
+}
+
+/// Like `fetch`, but blocks until the file is downloaded. The request runs on
+/// a runtime of its own thread, so this also works inside another runtime.
+fn fetch_blocking(url: &str) -> error::Result<String> {
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|e| Error::Io(format!("Failed to fetch {}: {}", url, e)))?
+                    .block_on(fetch(url))
+            })
+            .join()
+            .unwrap_or_else(|_| Err(Error::Io(format!("Failed to fetch {}", url))))
+    })
+}
+
+/// Reads a project file from disk, or fetches it if `location` is a url
+fn read_location(location: &str) -> error::Result<String> {
+    if Url::parse(location).is_ok() {
+        return fetch_blocking(location);
     }
+    fs::read_to_string(location)
+        .map_err(|e| Error::Project(format!("Failed to open project file {}: {}", location, e)))
 }
 
-fn parse_project_text(project: &str, url: &str) -> Result<ProjectConfiguration, String> {
-    match serde_yaml::from_str(project) {
-        Ok(options) => Ok(ProjectConfiguration {
-            options,
-            path: None,
-            url: Some(url.to_string()),
-        }),
-        Err(e) => Err(format!("Failed to parse project file: {}", e)),
+/// Finds an included project: a url, a path relative to the including file
+/// or the name of a project in the projects dir. Paths are canonicalized so
+/// cycles can be detected.
+fn resolve_include(include: &str, parent: &str) -> error::Result<String> {
+    if Url::parse(include).is_ok() {
+        return Ok(include.to_string());
     }
+    let file = if has_yaml_extension(include) {
+        include.to_string()
+    } else {
+        format!("{}.yaml", include)
+    };
+    if let Ok(base) = Url::parse(parent) {
+        return base.join(&file).map(|url| url.to_string()).map_err(|e| {
+            Error::Project(format!(
+                "Invalid include `{}` in {}: {}",
+                include, parent, e
+            ))
+        });
+    }
+    let path = if has_yaml_extension(include) {
+        Path::new(parent)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(file)
+    } else {
+        Path::new(&get_projects_path()?).join(file)
+    };
+    fs::canonicalize(&path)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| {
+            Error::Project(format!(
+                "Failed to open {} included from {}: {}",
+                path.display(),
+                parent,
+                e
+            ))
+        })
 }
 
-fn parse_project_file_from_path(path: &Path) -> Result<ProjectConfiguration, String> {
-    match fs::File::open(path) {
-        Ok(file) => parse_project_file(&file, path),
-        Err(e) => Err(format!("Failed to open project file: {}", e)),
+/// Merges the projects included by the project's `options` into it: their
+/// steps go before the project's own and their `vars` and `inputs` are
+/// merged, the including project's taking precedence. The included files are
+/// appended to `source`. `stack` holds the files being merged to detect
+/// cycles, `merged` the files already merged so a file included twice, e.g.
+/// by two includes, only runs once.
+fn merge_includes(
+    options: &mut YamlConfiguration,
+    source: &mut String,
+    location: &str,
+    stack: &mut Vec<String>,
+    merged: &mut BTreeSet<String>,
+) -> error::Result<()> {
+    let mut steps = Vec::new();
+    let mut vars = BTreeMap::new();
+    let mut inputs: Vec<Input> = Vec::new();
+    for include in &options.include {
+        let included = resolve_include(include, location)?;
+        if stack.contains(&included) {
+            stack.push(included);
+            return Err(Error::Project(format!(
+                "Projects include each other: {}",
+                stack.join(" -> ")
+            )));
+        }
+        if !merged.insert(included.clone()) {
+            continue;
+        }
+        let mut included_source = read_location(&included)?;
+        let mut included_options = validation::read(&included_source, &included)?;
+        stack.push(included.clone());
+        merge_includes(
+            &mut included_options,
+            &mut included_source,
+            &included,
+            stack,
+            merged,
+        )?;
+        stack.pop();
+        for mut step in included_options.steps {
+            step.file.get_or_insert_with(|| included.clone());
+            steps.push(step);
+        }
+        vars.extend(included_options.vars);
+        for input in included_options.inputs {
+            inputs.retain(|i| i.name != input.name);
+            inputs.push(input);
+        }
+        source.push_str(&format!(
+            "\n# included from {}\n{}",
+            included, included_source
+        ));
     }
+    steps.append(&mut options.steps);
+    options.steps = steps;
+    vars.append(&mut options.vars);
+    options.vars = vars;
+    inputs.retain(|i| options.inputs.iter().all(|own| own.name != i.name));
+    inputs.append(&mut options.inputs);
+    options.inputs = inputs;
+    Ok(())
+}
+
+/// Parses a project and the projects it includes. Returns the options and
+/// the sources of all files.
+fn parse_project(project: &str, location: &str) -> error::Result<(YamlConfiguration, String)> {
+    let mut options = validation::read(project, location)?;
+    let mut source = project.to_string();
+    let root = fs::canonicalize(location)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| location.to_string());
+    merge_includes(
+        &mut options,
+        &mut source,
+        location,
+        &mut vec![root],
+        &mut BTreeSet::new(),
+    )?;
+    Ok((validation::validate(options, location)?, source))
+}
+
+fn parse_project_file(project: &str, file_path: &Path) -> error::Result<ProjectConfiguration> {
+    let path = file_path.to_string_lossy().to_string();
+    let (options, source) = parse_project(project, &path)?;
+    Ok(ProjectConfiguration {
+        options,
+        source,
+        path: Some(path),
+        url: None,
+    })
+}
+
+fn parse_project_text(project: &str, url: &str) -> error::Result<ProjectConfiguration> {
+    let (options, source) = parse_project(project, url)?;
+    Ok(ProjectConfiguration {
+        options,
+        source,
+        path: None,
+        url: Some(url.to_string()),
+    })
+}
+
+fn parse_project_file_from_path(path: &Path) -> error::Result<ProjectConfiguration> {
+    let project = read_location(&path.to_string_lossy())?;
+    parse_project_file(&project, path)
 }
 
 fn has_yaml_extension(s: &str) -> bool {
@@ -245,48 +1057,289 @@ fn is_yaml(entry: &DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-pub fn get_all() -> Result<Vec<ProjectConfiguration>, String> {
-    let projects_path = get_projects_path();
+pub fn get_all() -> error::Result<Vec<ProjectConfiguration>> {
+    let projects_path = get_projects_path()?;
     let mut projects: Vec<ProjectConfiguration> = Vec::new();
 
     let mut walker = WalkDir::new(projects_path).into_iter();
     loop {
         let entry = match walker.next() {
             None => break,
-            Some(Err(e)) => return Err(format!("Failed to walk projects directory: {}", e)),
+            Some(Err(e)) => {
+                return Err(Error::Io(format!(
+                    "Failed to walk projects directory: {}",
+                    e
+                )))
+            }
             Some(Ok(entry)) => entry,
         };
         if !is_yaml(&entry) {
             continue;
         }
-        match parse_project_file_from_path(entry.path()) {
-            Ok(p) => projects.push(p),
-            Err(e) => return Err(e),
-        }
+        projects.push(parse_project_file_from_path(entry.path())?);
     }
 
     Ok(projects)
 }
 
-#[tokio::main]
-pub async fn get(name: &str) -> Result<ProjectConfiguration, String> {
+pub fn get_json_schema() -> String {
+    let mut schema = schemars::schema_for!(YamlConfiguration);
+    // Allowed next to the fields, see `ExtensionKey`
+    schema
+        .schema
+        .object()
+        .pattern_properties
+        .insert("^x-".to_string(), Schema::Bool(true));
+    serde_json::to_string_pretty(&schema).unwrap()
+}
+
+pub fn get(name: &str) -> error::Result<ProjectConfiguration> {
     if Url::parse(name).is_ok() {
-        println!(
+        // stderr, stdout may carry the events
+        eprintln!(
             "{}",
             "Project URL detected, fetching project from url...".bold()
         );
-        let res = reqwest::get(name)
-            .await
-            .expect("Failed to fetch project from url");
-        let body = res.text().await.expect("Failed to read project response");
-        return parse_project_text(&body, name);
+        return parse_project_text(&fetch_blocking(name)?, name);
     }
     let name_path = Path::new(name);
     if name_path.exists() && has_yaml_extension(name) {
         return parse_project_file_from_path(name_path);
     }
-    let projects_path = get_projects_path();
+    let projects_path = get_projects_path()?;
     let mut path_buf = Path::new(&projects_path).join(name);
     path_buf.set_extension("yaml");
     parse_project_file_from_path(path_buf.as_path())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(descriptions: &[&str]) -> ProjectConfiguration {
+        let steps: String = descriptions
+            .iter()
+            .map(|d| {
+                format!(
+                    "  - description: {}\n    run:\n      - note: a\n      - note: b\n",
+                    d
+                )
+            })
+            .collect();
+        let source = format!("name: p\ndescription: d\nsteps:\n{}", steps);
+        ProjectConfiguration {
+            options: serde_yaml::from_str(&source).unwrap(),
+            source,
+            path: None,
+            url: None,
+        }
+    }
+
+    /// Progress saved at `step` and `tool` of `config`
+    fn progress(config: &ProjectConfiguration, step: usize, tool: usize) -> ProjectProgress {
+        let step_config = config.options.steps.get(step);
+        ProjectProgress {
+            id: 1,
+            project_id: 1,
+            step: step as i32,
+            tool: tool as i32,
+            tool_step: 0,
+            step_key: step_config.map(|s| s.get_key()),
+            tool_key: step_config
+                .and_then(|s| s.run.get(tool))
+                .map(|t| t.get_key()),
+        }
+    }
+
+    #[test]
+    fn starts_over_when_completed() {
+        let config = project(&["one", "two"]);
+        assert_eq!(config.get_start_position(None), Ok((0, 0, 0)));
+        let saved = progress(&config, 2, 0);
+        assert_eq!(config.get_start_position(Some(&saved)), Ok((0, 0, 0)));
+    }
+
+    #[test]
+    fn follows_inserted_steps() {
+        let saved = progress(&project(&["one", "two", "three"]), 1, 1);
+        let config = project(&["zero", "one", "two", "three"]);
+        assert_eq!(config.get_start_position(Some(&saved)), Ok((2, 1, 0)));
+    }
+
+    #[test]
+    fn follows_removed_steps_past_the_new_end() {
+        let saved = progress(&project(&["1", "2", "3", "4", "5"]), 4, 0);
+        let config = project(&["3", "4", "5"]);
+        assert_eq!(config.get_start_position(Some(&saved)), Ok((2, 0, 0)));
+    }
+
+    #[test]
+    fn follows_reordered_steps() {
+        let saved = progress(&project(&["one", "two", "three"]), 0, 1);
+        let config = project(&["three", "two", "one"]);
+        assert_eq!(config.get_start_position(Some(&saved)), Ok((2, 1, 0)));
+    }
+
+    #[test]
+    fn guesses_when_the_step_is_gone() {
+        let saved = progress(&project(&["1", "2", "3", "4", "5"]), 4, 1);
+        let config = project(&["1", "2"]);
+        assert_eq!(config.get_start_position(Some(&saved)), Err((1, 1, 0)));
+    }
+
+    /// Writes `files` to a new directory and parses the first one
+    fn parse_files(dir: &str, files: &[(&str, &str)]) -> error::Result<YamlConfiguration> {
+        let dir = env::temp_dir().join(format!("siu-{}-{}", dir, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        let path = dir.join(files[0].0).to_string_lossy().to_string();
+        let result = parse_project(files[0].1, &path).map(|(options, _)| options);
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    fn step(description: &str) -> String {
+        format!(
+            "  - description: {}\n    run:\n      - note: {}\n",
+            description, description
+        )
+    }
+
+    #[test]
+    fn runs_included_steps_first_and_names_their_file() {
+        let options = parse_files(
+            "include",
+            &[
+                (
+                    "main.yaml",
+                    &format!(
+                        "name: main\ndescription: d\ninclude: [base.yaml]\nsteps:\n{}",
+                        step("own")
+                    ),
+                ),
+                (
+                    "base.yaml",
+                    &format!("name: base\ndescription: d\nsteps:\n{}", step("base")),
+                ),
+            ],
+        )
+        .unwrap();
+        let steps: Vec<(&str, String)> = options
+            .steps
+            .iter()
+            .map(|s| (s.description.as_str(), s.get_attribution()))
+            .collect();
+        assert_eq!(steps[0].0, "base");
+        assert!(steps[0].1.starts_with(" (from /") && steps[0].1.ends_with("base.yaml)"));
+        assert_eq!(steps[1], ("own", String::new()));
+    }
+
+    #[test]
+    fn merges_a_file_included_twice_once() {
+        let options = parse_files(
+            "diamond",
+            &[
+                (
+                    "main.yaml",
+                    "name: main\ndescription: d\ninclude: [a.yaml, b.yaml]\nsteps: []\n",
+                ),
+                (
+                    "a.yaml",
+                    &format!(
+                        "name: a\ndescription: d\ninclude: [shared.yaml]\nsteps:\n{}",
+                        step("a")
+                    ),
+                ),
+                (
+                    "b.yaml",
+                    &format!(
+                        "name: b\ndescription: d\ninclude: [shared.yaml]\nsteps:\n{}",
+                        step("b")
+                    ),
+                ),
+                (
+                    "shared.yaml",
+                    &format!("name: shared\ndescription: d\nsteps:\n{}", step("shared")),
+                ),
+            ],
+        )
+        .unwrap();
+        let descriptions: Vec<&str> = options
+            .steps
+            .iter()
+            .map(|s| s.description.as_str())
+            .collect();
+        assert_eq!(descriptions, vec!["shared", "a", "b"]);
+    }
+
+    #[test]
+    fn rejects_projects_including_each_other() {
+        let result = parse_files(
+            "cycle",
+            &[
+                (
+                    "a.yaml",
+                    &format!(
+                        "name: a\ndescription: d\ninclude: [b.yaml]\nsteps:\n{}",
+                        step("a")
+                    ),
+                ),
+                (
+                    "b.yaml",
+                    &format!(
+                        "name: b\ndescription: d\ninclude: [a.yaml]\nsteps:\n{}",
+                        step("b")
+                    ),
+                ),
+            ],
+        );
+        match result {
+            Err(Error::Project(message)) => {
+                assert!(message.starts_with("Projects include each other: /"));
+                assert!(message.ends_with("a.yaml"));
+            }
+            other => panic!("unexpected result: {:?}", other.map(|o| o.steps.len())),
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_inside_a_runtime() {
+        match read_location("http://127.0.0.1:9/project.yaml") {
+            Err(Error::Project(message)) => {
+                assert!(message.starts_with("Failed to fetch project from"))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fills_in_variables_once_and_keeps_the_name() {
+        let source = "name: '{{ who }}'\ndescription: '{{ a }}'\nvars:\n  who: me\n  a: '{{ b }}'\n  b: x\nsteps:\n  - description: d\n    run:\n      - note: '{{ who }}'\n";
+        let (options, source) = parse_project(source, "project.yaml").unwrap();
+        let config = ProjectConfiguration {
+            options,
+            source,
+            path: None,
+            url: None,
+        };
+        assert_eq!(config.options.description, "{{ a }}");
+        let config = config
+            .with_vars(&BTreeMap::new(), &BTreeMap::new())
+            .unwrap();
+        assert_eq!(config.options.name, "{{ who }}");
+        assert_eq!(config.options.description, "{{ b }}");
+    }
+
+    #[test]
+    fn uses_variables_in_strings_only() {
+        let source = "name: p\ndescription: d\nvars:\n  java: '17'\nsteps:\n  - description: d\n    run:\n      - jdk: { version: '{{ java }}' }\n";
+        match parse_project(source, "project.yaml") {
+            Err(Error::Parse(message)) => {
+                assert!(message.contains("did not match any variant of untagged enum Jdk"))
+            }
+            other => panic!("unexpected result: {:?}", other.map(|(o, _)| o.name)),
+        }
+    }
+}
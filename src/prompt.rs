@@ -0,0 +1,90 @@
+use std::io;
+
+use clap::ValueEnum;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input as Text, Select};
+
+use crate::error::{Error, Result};
+use crate::inputs::{Input, InputType};
+
+/// What to do with `pause` tools when running non-interactively
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug, Default)]
+pub enum PausePolicy {
+    /// Continue with the next tool
+    #[default]
+    Continue,
+    /// Save progress and exit, like answering "no"
+    Stop,
+    /// Exit with an error
+    Fail,
+}
+
+/// Asks a yes/no question
+pub fn confirm(prompt: &str) -> Result<bool> {
+    let theme = ColorfulTheme::default();
+    Confirm::with_theme(&theme)
+        .with_prompt(prompt)
+        .interact()
+        .map_err(input_error)
+}
+
+/// Asks whether to continue at a pause. Returns false if the user wants to
+/// finish later.
+pub fn pause() -> Result<bool> {
+    let theme = ColorfulTheme::default();
+    eprintln!("\nAre you ready to continue?");
+    let answer = Confirm::with_theme(&theme)
+        .with_prompt("Press Enter or 'y' to continue or Esc, 'q' or 'n' to exit and finish later")
+        .default(true)
+        .interact_opt()
+        .map_err(input_error)?;
+    Ok(answer.unwrap_or(false))
+}
+
+fn input_error(e: io::Error) -> Error {
+    Error::Input(format!("Failed to read user input: {}", e))
+}
+
+/// Lets the user pick one of `items`. Returns None if they cancelled.
+pub fn select(prompt: &str, items: &[String]) -> Result<Option<usize>> {
+    let theme = ColorfulTheme::default();
+    Select::with_theme(&theme)
+        .with_prompt(prompt)
+        .items(items)
+        .default(0)
+        .interact_opt()
+        .map_err(input_error)
+}
+
+/// Asks for the answer to one of the project's inputs, suggesting `default`
+pub fn input(input: &Input, default: Option<&str>) -> Result<String> {
+    let prompt = input.get_prompt();
+    let theme = ColorfulTheme::default();
+    match input.kind {
+        InputType::Confirm => Confirm::with_theme(&theme)
+            .with_prompt(prompt)
+            .default(default == Some("true"))
+            .interact()
+            .map(|answer| answer.to_string()),
+        InputType::Select => {
+            let options = input.options.clone().unwrap_or_default();
+            let selected = options.iter().position(|o| Some(o.as_str()) == default);
+            Select::with_theme(&theme)
+                .with_prompt(prompt)
+                .items(&options)
+                .default(selected.unwrap_or(0))
+                .interact()
+                .map(|index| options[index].clone())
+        }
+        InputType::Text | InputType::Email | InputType::Number => {
+            let mut text = Text::<String>::with_theme(&theme);
+            text.with_prompt(prompt)
+                .allow_empty(input.optional)
+                .validate_with(|answer: &String| input.validate(answer));
+            if let Some(default) = default {
+                text.default(default.to_string());
+            }
+            text.interact_text()
+        }
+    }
+    .map_err(input_error)
+}
@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+use crate::db::get_cache_path;
+use crate::error::{Error, Result};
+use crate::events::{Event, Observer, OutputStream};
+
+/// Writes every event of a run, including the output of the commands it
+/// runs, to a timestamped log file in `~/.cache/siu-logs`, so a failed run
+/// can be reported by sending a single file.
+pub struct RunLog {
+    path: PathBuf,
+    file: RefCell<File>,
+}
+
+fn get_file_name(project: &str) -> String {
+    let project: String = project
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{}-{}.log", project, Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+impl RunLog {
+    pub fn create(project: &str) -> Result<Self> {
+        let dir = Path::new(&get_cache_path()?).join("siu-logs");
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::Io(format!("Failed to create the logs dir: {}", e)))?;
+        let path = dir.join(get_file_name(project));
+        let file = File::create(&path).map_err(|e| {
+            Error::Io(format!(
+                "Failed to create log file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(Self {
+            path,
+            file: RefCell::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn format_event(event: &Event) -> String {
+    match event {
+        Event::ProjectStarted {
+            project,
+            description,
+            steps,
+        } => format!(
+            "project \"{}\" started ({} steps): {}",
+            project, steps, description
+        ),
+        Event::ProjectResumed { step, tool } => {
+            format!("resuming at step {}, tool {}", step + 1, tool + 1)
+        }
+        Event::StepStarted { step, description } => {
+            format!("step {}: {}", step + 1, description)
+        }
+        Event::ToolStarted { step, tool, name } => {
+            format!("step {} tool {} {} started", step + 1, tool + 1, name)
+        }
+        Event::StepSkipped {
+            step,
+            description,
+            reason,
+        } => format!("step {}: {} skipped, {}", step + 1, description, reason),
+        Event::ToolSkipped {
+            step,
+            tool,
+            name,
+            reason,
+        } => format!(
+            "step {} tool {} {} skipped, {}",
+            step + 1,
+            tool + 1,
+            name,
+            reason
+        ),
+        Event::CommandSpawned { command } => format!("$ {}", command),
+        Event::Output {
+            stream: OutputStream::Stdout,
+            line,
+        } => format!("stdout | {}", line),
+        Event::Output {
+            stream: OutputStream::Stderr,
+            line,
+        } => format!("stderr | {}", line),
+        Event::ToolFinished { step, tool, name } => {
+            format!("step {} tool {} {} done", step + 1, tool + 1, name)
+        }
+        Event::ToolPaused { step, tool, name } => {
+            format!("step {} tool {} {} paused", step + 1, tool + 1, name)
+        }
+        Event::ToolFailed {
+            step,
+            tool,
+            name,
+            error,
+        } => format!(
+            "step {} tool {} {} failed: {}",
+            step + 1,
+            tool + 1,
+            name,
+            error
+        ),
+        Event::ProjectCompleted { project } => format!("project \"{}\" completed", project),
+        Event::ProjectPaused { project } => format!("project \"{}\" paused", project),
+        Event::ProjectFailed {
+            project,
+            error,
+            exit_code,
+        } => format!(
+            "project \"{}\" failed with exit code {}: {}",
+            project, exit_code, error
+        ),
+    }
+}
+
+impl Observer for RunLog {
+    fn on_event(&self, event: &Event) {
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        let mut file = self.file.borrow_mut();
+        // Multi-line messages, like a failed command's stderr, keep the
+        // timestamp on every line so the log stays easy to grep
+        for line in format_event(event).lines() {
+            let _ = writeln!(file, "[{}] {}", time, line);
+        }
+    }
+}
@@ -1,10 +1,31 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    completed_steps (id) {
+        id -> Integer,
+        project_id -> Integer,
+        step_key -> Text,
+        completed_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    project_inputs (id) {
+        id -> Integer,
+        project_id -> Integer,
+        name -> Text,
+        value -> Text,
+        updated_at -> BigInt,
+    }
+}
+
 diesel::table! {
     projects (id) {
         id -> Integer,
         name -> Text,
         path -> Text,
+        content_hash -> Nullable<Text>,
+        content -> Nullable<Text>,
     }
 }
 
@@ -15,10 +36,47 @@ diesel::table! {
         step -> Integer,
         tool -> Integer,
         tool_step -> Integer,
+        step_key -> Nullable<Text>,
+        tool_key -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    runs (id) {
+        id -> Integer,
+        project_id -> Integer,
+        started_at -> BigInt,
+        finished_at -> Nullable<BigInt>,
+        status -> Text,
+        error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    tool_runs (id) {
+        id -> Integer,
+        run_id -> Integer,
+        step -> Integer,
+        tool -> Integer,
+        tool_step -> Integer,
+        tool_name -> Text,
+        started_at -> BigInt,
+        finished_at -> Nullable<BigInt>,
+        status -> Text,
+        error -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(completed_steps -> projects (project_id));
+diesel::joinable!(project_inputs -> projects (project_id));
+diesel::joinable!(runs -> projects (project_id));
+diesel::joinable!(tool_runs -> runs (run_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    completed_steps,
+    project_inputs,
     projects,
     projects_progress,
+    runs,
+    tool_runs,
 );
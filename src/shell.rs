@@ -1,11 +1,10 @@
 use std::{
     env, fs,
     path::{Path, PathBuf},
-    process::{exit, Command},
 };
 
-use dialoguer::{theme::ColorfulTheme, Confirm};
-use owo_colors::OwoColorize;
+use crate::error::{self, Error};
+use crate::ui::Ui;
 
 pub enum Shell {
     Bash,
@@ -41,60 +40,100 @@ impl Shell {
     }
 
     pub fn config_exists(&self) -> bool {
-        return Path::new(&self.get_config_path()).exists();
+        Path::new(&self.get_config_path()).exists()
     }
 
     pub fn config_contains_string(&self, s: &str) -> bool {
         fs::read_to_string(self.get_config_path_str())
-            .unwrap()
-            .contains(s)
+            .map(|contents| contents.contains(s))
+            .unwrap_or(false)
     }
 
-    pub fn has_command(&self, cmd: &str) -> bool {
-        let output = Command::new("which")
-            .arg(cmd)
-            .output()
-            .expect("failed to execute process");
-        output.status.success()
+    /// Whether the config has `line` as a whole line
+    pub fn config_contains_line(&self, line: &str) -> bool {
+        fs::read_to_string(self.get_config_path_str())
+            .map(|contents| contents.lines().any(|l| l.trim_end() == line))
+            .unwrap_or(false)
+    }
+
+    /// Replaces every whole line `old` in the config with `new`
+    pub fn replace_in_config(&self, ui: &dyn Ui, old: &str, new: &str) -> error::Result<()> {
+        let path = &self.get_config_path();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::Io(format!("Failed to read shell config file: {}", e)))?;
+        let mut replaced: String = contents
+            .lines()
+            .map(|line| if line.trim_end() == old { new } else { line })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if contents.ends_with('\n') {
+            replaced.push('\n');
+        }
+
+        ui.info(&format!(
+            "We will replace the following line in your shell config file:\n{}\nwith:\n{}",
+            old, new
+        ));
+        if ui.confirm("is that okay?")? {
+            fs::write(path, replaced.as_bytes())
+                .map_err(|e| Error::Io(format!("Failed to write to shell config file: {}", e)))?;
+            return Ok(());
+        }
+        Err(Error::Aborted(
+            "Make sure to replace that line in your shell config before continuing.".to_string(),
+        ))
     }
 
-    pub fn write_to_config(&self, s: &str) -> Result<(), String> {
+    pub fn write_to_config(&self, ui: &dyn Ui, s: &str) -> error::Result<()> {
         let path = &self.get_config_path();
-        let file = fs::read(path).expect("Failed to read shell config file");
+        // Fresh machines and containers might not have a shell config yet
+        let file = if self.config_exists() {
+            fs::read(path)
+                .map_err(|e| Error::Io(format!("Failed to read shell config file: {}", e)))?
+        } else {
+            Vec::new()
+        };
 
         let mut contents = String::from_utf8_lossy(&file).to_string();
         if !self.config_contains_string(s) {
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
             contents.push_str(s);
+            contents.push('\n');
 
-            let theme = ColorfulTheme::default();
-            println!("\nWe will add the following line to your shell config file: ");
-            println!("{}", s.green());
-            if Confirm::with_theme(&theme)
-                .with_prompt("is that okay?")
-                .interact()
-                .expect("Failed to read user input")
-            {
-                fs::write(path, contents.as_bytes()).expect("Failed to write to shell config file");
+            ui.info(&format!(
+                "We will add the following line to your shell config file:\n{}",
+                s
+            ));
+            if ui.confirm("is that okay?")? {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| {
+                        Error::Io(format!("Failed to create shell config dir: {}", e))
+                    })?;
+                }
+                fs::write(path, contents.as_bytes()).map_err(|e| {
+                    Error::Io(format!("Failed to write to shell config file: {}", e))
+                })?;
                 return Ok(());
             }
-            println!("Make sure to add that it to you shell config before continuing.");
-            exit(1);
+            return Err(Error::Aborted(
+                "Make sure to add that line to your shell config before continuing.".to_string(),
+            ));
         }
         Ok(())
     }
 }
 
-pub fn get_current() -> Result<Shell, &'static str> {
-    let env_var_key = "SHELL";
-    match env::var(env_var_key) {
-        Ok(val) => {
-            if val.contains("fish") {
-                return Ok(Shell::Fish);
-            } else if val.contains("zsh") {
-                return Ok(Shell::Zsh);
-            }
-            Ok(Shell::Bash)
-        }
-        Err(e) => panic!("couldn't interpret {env_var_key}: {e}"),
+pub fn get_current() -> Shell {
+    // Containers and CI runners often don't set SHELL
+    let val = env::var_os("SHELL").unwrap_or_default();
+    let val = val.to_string_lossy();
+    if val.contains("fish") {
+        Shell::Fish
+    } else if val.contains("zsh") {
+        Shell::Zsh
+    } else {
+        Shell::Bash
     }
 }
@@ -1,7 +1,13 @@
 pub mod chezmoi;
 pub mod homebrew;
-pub mod java11;
+pub mod jdk;
+pub mod node;
+pub mod package;
+pub mod package_manager;
 pub mod pnpm;
 pub mod rbenv;
+pub mod run;
+pub mod runner;
+pub mod system_packages;
 pub mod types;
 pub mod yarn;
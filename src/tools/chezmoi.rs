@@ -1,12 +1,30 @@
-use owo_colors::OwoColorize;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use std::path::Path;
 
-use super::{homebrew::Homebrew, types::Tool};
+use crate::error::Result;
+use crate::events::Event;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+use super::{
+    package_manager::{PackageManager, SystemPackage},
+    runner::{CommandRunner, CommandSpec},
+    types::{Context, PlannedAction, Tool},
+};
+
+const CHEZMOI: SystemPackage = SystemPackage {
+    command: "chezmoi",
+    brew: Some("chezmoi"),
+    apt: None,
+    dnf: None,
+    pacman: Some("chezmoi"),
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Chezmoi {
+    /// GitHub repository holding your dotfiles, e.g. `user/dotfiles`
     repo: String,
+    /// Applies the dotfiles right after initialising the source directory
     apply: Option<bool>,
 }
 
@@ -14,13 +32,20 @@ impl Chezmoi {
     fn get_repo_url(&self) -> String {
         format!("git@github.com/{}.git", self.repo)
     }
+
+    fn get_command(&self) -> String {
+        let apply = match self.apply.unwrap_or(false) {
+            false => "",
+            true => "--apply ",
+        };
+        format!("chezmoi init {}{}", apply, self.get_repo_url())
+    }
 }
 
 impl Tool for Chezmoi {
-    fn install(&self, tool_step: usize) -> Result<bool, String> {
-        let brew = Homebrew::Packages(String::from("chezmoi"));
-        brew.install(tool_step)?;
-        self.print_command();
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        PackageManager::get(ctx.runner)?.install_package(ctx, &CHEZMOI)?;
+        self.print_command(ctx);
         let repo_url = self.get_repo_url();
         let mut args = vec!["init", &repo_url];
         if let Some(apply) = self.apply {
@@ -29,27 +54,99 @@ impl Tool for Chezmoi {
                 args.swap(1, 2);
             }
         }
-        let mut child = Command::new("chezmoi").args(args).spawn().unwrap();
-        match child.wait() {
-            Ok(status) => {
-                if !status.success() {
-                    return Err("Failed to install chezmoi".to_string());
-                }
-            }
-            Err(e) => return Err(format!("Failed to run command: {}", e)),
-        }
+        ctx.runner
+            .run_checked(&CommandSpec::new("chezmoi").args(args), &|stream, line| {
+                ctx.output(stream, line)
+            })?;
 
         Ok(false)
     }
 
-    fn print_command(&self) {
-        let apply = match self.apply.unwrap_or(false) {
-            false => "",
-            true => "--apply ",
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self.get_command(),
+        });
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        let mut actions = PackageManager::current().plan_package(&CHEZMOI);
+        actions.push(PlannedAction::Command(self.get_command()));
+        actions
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        match runner.get_output(&CommandSpec::new("chezmoi").arg("source-path")) {
+            Some(source_path) => Path::new(source_path.trim()).join(".git").exists(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn installs_chezmoi_then_inits_the_repo() {
+        let runner = ScriptedRunner::new();
+        let chezmoi = Chezmoi {
+            repo: String::from("user/dotfiles"),
+            apply: Some(true),
         };
-        println!(
-            "\n{}\n",
-            format!("chezmoi init {}{}", apply, self.get_repo_url()).italic()
+        chezmoi
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        assert_eq!(
+            runner.argvs(),
+            vec![
+                vec!["which", "brew"],
+                vec!["brew", "install", "chezmoi"],
+                vec![
+                    "chezmoi",
+                    "init",
+                    "--apply",
+                    "git@github.com/user/dotfiles.git"
+                ],
+            ]
         );
     }
+
+    #[test]
+    fn stops_when_brew_fails() {
+        let runner = ScriptedRunner::new().on("brew", 1, "", "");
+        let chezmoi = Chezmoi {
+            repo: String::from("user/dotfiles"),
+            apply: None,
+        };
+        assert!(chezmoi
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .is_err());
+        assert_eq!(runner.argvs().len(), 2);
+    }
+
+    #[test]
+    fn uses_the_package_manager_of_the_host() {
+        let runner = ScriptedRunner::new()
+            .on("which brew", 1, "", "")
+            .on("which apt-get", 1, "", "")
+            .on("which dnf", 1, "", "")
+            .on("id -u", 0, "1000\n", "");
+        let chezmoi = Chezmoi {
+            repo: String::from("user/dotfiles"),
+            apply: None,
+        };
+        chezmoi
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        assert!(runner.argvs().contains(&vec![
+            "sudo".to_string(),
+            "pacman".to_string(),
+            "-S".to_string(),
+            "--needed".to_string(),
+            "--noconfirm".to_string(),
+            "chezmoi".to_string()
+        ]));
+    }
 }
@@ -1,41 +1,315 @@
-use owo_colors::OwoColorize;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 
-use super::types::Tool;
+use crate::error::Result;
+use crate::events::Event;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+use super::{
+    runner::{CommandRunner, CommandSpec},
+    types::{expand_home, Context, PlannedAction, Tool},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BrewPackageOptions {
+    /// Formula or cask to install
+    name: String,
+    /// Installs a versioned formula, e.g. `18` for `node@18`
+    version: Option<String>,
+    /// Extra arguments passed to `brew install`, e.g. `--HEAD`
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum BrewPackage {
+    Name(String),
+    Options(BrewPackageOptions),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HomebrewOptions {
+    /// Taps added before installing, e.g. `homebrew/cask-fonts`
+    #[serde(default)]
+    taps: Vec<String>,
+    /// Formulae to install
+    #[serde(default)]
+    formulae: Vec<BrewPackage>,
+    /// Casks to install
+    #[serde(default)]
+    casks: Vec<BrewPackage>,
+    /// Brewfile installed with `brew bundle`, `~` is expanded to your home
+    /// directory
+    brewfile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub enum Homebrew {
+    /// Space separated list of packages passed to `brew install`
     Packages(String),
+    /// List of formulae
+    List(Vec<BrewPackage>),
+    Options(HomebrewOptions),
+}
+
+impl BrewPackage {
+    /// Reads packages with their flags, e.g. `--cask temurin`. Every name
+    /// becomes a package of its own with all the flags.
+    pub fn parse(spec: &str) -> Vec<Self> {
+        let (args, names): (Vec<&str>, Vec<&str>) =
+            spec.split_whitespace().partition(|p| p.starts_with('-'));
+        names
+            .into_iter()
+            .map(|name| match args.is_empty() {
+                true => BrewPackage::Name(name.to_string()),
+                false => BrewPackage::Options(BrewPackageOptions {
+                    name: name.to_string(),
+                    version: None,
+                    args: args.iter().map(|a| a.to_string()).collect(),
+                }),
+            })
+            .collect()
+    }
+
+    fn get_name(&self) -> String {
+        match self {
+            BrewPackage::Name(name) => name.clone(),
+            BrewPackage::Options(options) => match &options.version {
+                Some(version) => format!("{}@{}", options.name, version),
+                None => options.name.clone(),
+            },
+        }
+    }
+
+    fn get_args(&self) -> &[String] {
+        match self {
+            BrewPackage::Name(_) => &[],
+            BrewPackage::Options(options) => &options.args,
+        }
+    }
+}
+
+/// `brew install` commands for `packages`: one for every package with extra
+/// arguments and one for all the others
+pub fn get_install_commands(packages: &[BrewPackage], cask: bool) -> Vec<CommandSpec> {
+    let install = || match cask {
+        true => CommandSpec::new("brew").args(["install", "--cask"]),
+        false => CommandSpec::new("brew").arg("install"),
+    };
+    let (plain, with_args): (Vec<&BrewPackage>, Vec<&BrewPackage>) =
+        packages.iter().partition(|p| p.get_args().is_empty());
+    let mut commands = Vec::new();
+    if !plain.is_empty() {
+        commands.push(install().args(plain.iter().map(|p| p.get_name())));
+    }
+    for package in with_args {
+        commands.push(install().args(package.get_args()).arg(&package.get_name()));
+    }
+    commands
 }
 
 impl Homebrew {
-    fn get_packages(&self) -> Vec<&str> {
-        let Homebrew::Packages(s) = self;
-        s.split(' ').collect::<Vec<&str>>()
+    fn get_options(&self) -> HomebrewOptions {
+        match self {
+            Homebrew::Packages(s) => HomebrewOptions {
+                formulae: s
+                    .split_whitespace()
+                    .map(|p| BrewPackage::Name(p.to_string()))
+                    .collect(),
+                ..Default::default()
+            },
+            Homebrew::List(formulae) => HomebrewOptions {
+                formulae: formulae.clone(),
+                ..Default::default()
+            },
+            Homebrew::Options(options) => options.clone(),
+        }
+    }
+
+    fn get_commands(&self) -> Vec<CommandSpec> {
+        let options = self.get_options();
+        let mut commands: Vec<CommandSpec> = options
+            .taps
+            .iter()
+            .map(|tap| CommandSpec::new("brew").args(["tap", tap]))
+            .collect();
+        commands.extend(get_install_commands(&options.formulae, false));
+        commands.extend(get_install_commands(&options.casks, true));
+        if let Some(brewfile) = &options.brewfile {
+            commands.push(CommandSpec::new("brew").args([
+                "bundle",
+                "--file",
+                &expand_home(brewfile),
+            ]));
+        }
+        commands
     }
 }
 
 impl Tool for Homebrew {
-    fn install(&self, _: usize) -> Result<bool, String> {
-        self.print_command();
-        let args = [Vec::from(["install"]), self.get_packages().to_owned()].concat();
-        let mut child = Command::new("brew").args(args).spawn().unwrap();
-        match child.wait() {
-            Ok(status) => {
-                if !status.success() {
-                    return Err("Failed to install brew packages".to_string());
-                }
-            }
-            Err(e) => return Err(format!("Failed to run command: {}", e)),
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        for command in self.get_commands() {
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+            ctx.runner
+                .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
         }
 
         Ok(false)
     }
 
-    fn print_command(&self) {
-        let Homebrew::Packages(s) = self;
-        println!("\n{}\n", format!("brew install {}", s).italic());
+    fn print_command(&self, ctx: &Context) {
+        for command in self.get_commands() {
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+        }
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        self.get_commands()
+            .into_iter()
+            .map(|command| PlannedAction::Command(command.to_string()))
+            .collect()
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let options = self.get_options();
+        if !options.taps.is_empty() {
+            let Some(taps) = runner.get_output(&CommandSpec::new("brew").arg("tap")) else {
+                return false;
+            };
+            let installed = |tap: &String| taps.lines().any(|t| t.eq_ignore_ascii_case(tap));
+            if !options.taps.iter().all(installed) {
+                return false;
+            }
+        }
+        for (packages, cask) in [(&options.formulae, false), (&options.casks, true)] {
+            if packages.is_empty() {
+                continue;
+            }
+            let mut list = CommandSpec::new("brew").arg("list");
+            if cask {
+                list = list.arg("--cask");
+            }
+            let list = list
+                .arg("--versions")
+                .args(packages.iter().map(|p| p.get_name()));
+            if runner.get_output(&list).is_none() {
+                return false;
+            }
+        }
+        match &options.brewfile {
+            Some(brewfile) => runner
+                .get_output(&CommandSpec::new("brew").args([
+                    "bundle",
+                    "check",
+                    "--file",
+                    &expand_home(brewfile),
+                ]))
+                .is_some(),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn installs_every_package() {
+        let runner = ScriptedRunner::new();
+        let brew = Homebrew::Packages(String::from("git wget"));
+        assert!(!brew.install(&Context::new(&runner, &SilentUi), 0).unwrap());
+        assert_eq!(runner.argvs(), vec![vec!["brew", "install", "git", "wget"]]);
+    }
+
+    #[test]
+    fn fails_with_the_brew_error() {
+        let runner =
+            ScriptedRunner::new().on("brew install", 1, "", "Error: No available formula\n");
+        let brew = Homebrew::Packages(String::from("nope"));
+        match brew.install(&Context::new(&runner, &SilentUi), 0) {
+            Err(Error::CommandFailed { status, stderr, .. }) => {
+                assert_eq!(status, Some(1));
+                assert!(stderr.contains("No available formula"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn checks_installed_versions() {
+        let brew = Homebrew::Packages(String::from("git wget"));
+        let runner = ScriptedRunner::new().on("brew list", 0, "git 2.41.0\nwget 1.21\n", "");
+        assert!(brew.check(&runner));
+        assert_eq!(
+            runner.argvs(),
+            vec![vec!["brew", "list", "--versions", "git", "wget"]]
+        );
+        let runner = ScriptedRunner::new().on("brew list", 1, "git 2.41.0\n", "");
+        assert!(!brew.check(&runner));
+    }
+
+    #[test]
+    fn parses_every_name_of_a_spec() {
+        let commands = get_install_commands(&BrewPackage::parse("--cask zulu11 temurin"), false);
+        let argvs: Vec<Vec<&str>> = commands.iter().map(|c| c.argv()).collect();
+        assert_eq!(
+            argvs,
+            vec![
+                vec!["brew", "install", "--cask", "zulu11"],
+                vec!["brew", "install", "--cask", "temurin"],
+            ]
+        );
+        assert_eq!(
+            BrewPackage::parse(" git  wget "),
+            vec![
+                BrewPackage::Name("git".to_string()),
+                BrewPackage::Name("wget".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn installs_taps_formulae_and_casks() {
+        let brew: Homebrew = serde_yaml::from_str(
+            "taps: [homebrew/cask-fonts]\nformulae:\n  - git\n  - { name: node, version: '18' }\n  - { name: neovim, args: [--HEAD] }\ncasks: [font-fira-code, iterm2]\nbrewfile: /tmp/Brewfile\n",
+        )
+        .unwrap();
+        let runner = ScriptedRunner::new();
+        brew.install(&Context::new(&runner, &SilentUi), 0).unwrap();
+        assert_eq!(
+            runner.argvs(),
+            vec![
+                vec!["brew", "tap", "homebrew/cask-fonts"],
+                vec!["brew", "install", "git", "node@18"],
+                vec!["brew", "install", "--HEAD", "neovim"],
+                vec!["brew", "install", "--cask", "font-fira-code", "iterm2"],
+                vec!["brew", "bundle", "--file", "/tmp/Brewfile"],
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_taps_and_casks() {
+        let brew: Homebrew =
+            serde_yaml::from_str("taps: [homebrew/cask-fonts]\ncasks: [iterm2]\n").unwrap();
+        let runner = ScriptedRunner::new().on("brew tap", 0, "homebrew/cask-fonts\n", "");
+        assert!(brew.check(&runner));
+        assert_eq!(
+            runner.argvs()[1],
+            vec!["brew", "list", "--cask", "--versions", "iterm2"]
+        );
+        let runner = ScriptedRunner::new().on("brew tap", 0, "homebrew/core\n", "");
+        assert!(!brew.check(&runner));
     }
 }
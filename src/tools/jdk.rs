@@ -0,0 +1,414 @@
+use std::env;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::events::Event;
+use crate::shell;
+
+use super::{
+    package_manager::{PackageManager, SystemPackage},
+    runner::{CommandRunner, CommandSpec, SystemRunner},
+    types::{Context, PlannedAction, Tool},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JdkVendor {
+    /// Azul Zulu
+    Zulu,
+    /// Eclipse Temurin
+    Temurin,
+    /// Amazon Corretto
+    Corretto,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JdkOptions {
+    /// Major Java version, e.g. `17`
+    version: u32,
+    /// Distribution to install. Defaults to Zulu on macOS and to the OpenJDK
+    /// of your Linux distribution. On Linux the vendor's
+    /// package repository has to be added first, e.g. with an `apt` item.
+    vendor: Option<JdkVendor>,
+    /// Makes this the JDK used by default: sets JAVA_HOME in your shell
+    /// config and, on Linux, the `java` alternative. Defaults to true, turn
+    /// it off when installing several JDKs.
+    default: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Jdk {
+    /// Major Java version, installed from the default distribution
+    Version(u32),
+    Options(JdkOptions),
+}
+
+/// Names of the JDK's package in every package manager
+struct JdkPackages {
+    brew: String,
+    apt: String,
+    dnf: String,
+    pacman: Option<String>,
+}
+
+impl JdkPackages {
+    fn get(&self) -> SystemPackage<'_> {
+        SystemPackage {
+            command: "java",
+            brew: Some(&self.brew),
+            apt: Some(&self.apt),
+            dnf: Some(&self.dnf),
+            pacman: self.pacman.as_deref(),
+        }
+    }
+}
+
+/// Package manager installing JDKs on `os`: Homebrew casks on macOS, the
+/// distribution's package manager on Linux even if Homebrew is installed
+fn detect_manager(runner: &dyn CommandRunner, os: &str) -> Option<PackageManager> {
+    match os {
+        "macos" => PackageManager::detect(runner).filter(|m| *m == PackageManager::Homebrew),
+        _ => PackageManager::detect_native(runner),
+    }
+}
+
+fn get_manager(runner: &dyn CommandRunner) -> Result<PackageManager> {
+    detect_manager(runner, env::consts::OS).ok_or_else(|| {
+        Error::MissingCommand(match env::consts::OS {
+            "macos" => "brew".to_string(),
+            _ => "a package manager (apt-get, dnf or pacman)".to_string(),
+        })
+    })
+}
+
+/// Debian names the JDK directories after the architecture
+fn get_debian_arch() -> &'static str {
+    match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
+
+impl Jdk {
+    /// The JDK `java11: true` installs
+    pub fn java11() -> Self {
+        Jdk::Version(11)
+    }
+
+    fn get_options(&self) -> JdkOptions {
+        match self {
+            Jdk::Version(version) => JdkOptions {
+                version: *version,
+                vendor: None,
+                default: None,
+            },
+            Jdk::Options(options) => options.clone(),
+        }
+    }
+
+    fn get_packages(&self) -> JdkPackages {
+        let JdkOptions {
+            version, vendor, ..
+        } = self.get_options();
+        match vendor {
+            None => JdkPackages {
+                brew: format!("--cask zulu@{}", version),
+                apt: format!("openjdk-{}-jdk", version),
+                dnf: format!("java-{}-openjdk-devel", version),
+                pacman: Some(format!("jdk{}-openjdk", version)),
+            },
+            Some(JdkVendor::Zulu) => JdkPackages {
+                brew: format!("--cask zulu@{}", version),
+                apt: format!("zulu{}-jdk", version),
+                dnf: format!("zulu{}-jdk", version),
+                pacman: None,
+            },
+            Some(JdkVendor::Temurin) => JdkPackages {
+                brew: format!("--cask temurin@{}", version),
+                apt: format!("temurin-{}-jdk", version),
+                dnf: format!("temurin-{}-jdk", version),
+                pacman: None,
+            },
+            Some(JdkVendor::Corretto) => JdkPackages {
+                brew: format!("--cask corretto@{}", version),
+                apt: format!("java-{}-amazon-corretto-jdk", version),
+                dnf: format!("java-{}-amazon-corretto-devel", version),
+                pacman: None,
+            },
+        }
+    }
+
+    /// Start of the name of the JDK's directory in `/usr/lib/jvm`
+    fn get_directory_prefix(&self) -> String {
+        let JdkOptions {
+            version, vendor, ..
+        } = self.get_options();
+        match vendor {
+            None => format!("java-{}-openjdk", version),
+            Some(JdkVendor::Zulu) => format!("zulu{}", version),
+            Some(JdkVendor::Temurin) => format!("temurin-{}", version),
+            Some(JdkVendor::Corretto) => format!("java-{}-amazon-corretto", version),
+        }
+    }
+
+    /// Where the packages usually install the JDK. On macOS, where Homebrew
+    /// installs it, this is a shell expression asking `/usr/libexec/java_home`.
+    fn get_known_java_home(&self, manager: PackageManager) -> String {
+        let version = self.get_options().version;
+        let prefix = self.get_directory_prefix();
+        match (manager, self.get_options().vendor) {
+            (PackageManager::Homebrew, _) => {
+                format!("$(/usr/libexec/java_home -v {})", version)
+            }
+            (PackageManager::Apt, None) => {
+                format!("/usr/lib/jvm/{}-{}", prefix, get_debian_arch())
+            }
+            (PackageManager::Apt, Some(JdkVendor::Zulu)) => {
+                format!("/usr/lib/jvm/{}-ca-{}", prefix, get_debian_arch())
+            }
+            (PackageManager::Apt, Some(JdkVendor::Temurin)) => {
+                format!("/usr/lib/jvm/{}-jdk-{}", prefix, get_debian_arch())
+            }
+            (PackageManager::Dnf, Some(JdkVendor::Temurin)) => {
+                format!("/usr/lib/jvm/{}-jdk", prefix)
+            }
+            _ => format!("/usr/lib/jvm/{}", prefix),
+        }
+    }
+
+    /// Finds the JDK among the `java` alternatives on Linux, falling back to
+    /// the known path
+    fn get_java_home(&self, runner: &dyn CommandRunner, manager: PackageManager) -> String {
+        if matches!(manager, PackageManager::Apt | PackageManager::Dnf) {
+            let prefix = self.get_directory_prefix();
+            let list = CommandSpec::new("update-alternatives").args(["--list", "java"]);
+            let found = runner.get_output(&list).and_then(|output| {
+                output.lines().find_map(|line| {
+                    let start = line.find(&format!("/{}", prefix))?;
+                    let end = line[start + 1..]
+                        .find('/')
+                        .map_or(line.len(), |end| start + 1 + end);
+                    Some(line[..end].to_string())
+                })
+            });
+            if let Some(java_home) = found {
+                return java_home;
+            }
+        }
+        self.get_known_java_home(manager)
+    }
+
+    fn get_shell_config(&self, shell: &shell::Shell, java_home: &str) -> String {
+        match shell {
+            shell::Shell::Fish => format!("set -x JAVA_HOME {}", java_home.replace("$(", "(")),
+            _ => format!("export JAVA_HOME=\"{}\"", java_home),
+        }
+    }
+
+    /// Lines `java11` used to write before it installed through `jdk`. A
+    /// default JDK replaces them instead of adding a conflicting line.
+    fn get_legacy_shell_configs(&self, shell: &shell::Shell) -> Vec<String> {
+        [
+            "/Library/Java/JavaVirtualMachines/zulu-11.jdk/Contents/Home".to_string(),
+            format!("/usr/lib/jvm/java-11-openjdk-{}", get_debian_arch()),
+            "/usr/lib/jvm/java-11-openjdk".to_string(),
+        ]
+        .iter()
+        .map(|java_home| self.get_shell_config(shell, java_home))
+        .collect()
+    }
+
+    /// Legacy `java11` line in the shell config that differs from `shell_config`
+    fn find_legacy_shell_config(&self, shell: &shell::Shell, shell_config: &str) -> Option<String> {
+        self.get_legacy_shell_configs(shell)
+            .into_iter()
+            .find(|line| line != shell_config && shell.config_contains_line(line))
+    }
+
+    /// Commands making the JDK the default `java`, to be run as root
+    fn get_default_commands(&self, manager: PackageManager, java_home: &str) -> Vec<CommandSpec> {
+        match manager {
+            PackageManager::Homebrew => Vec::new(),
+            PackageManager::Apt | PackageManager::Dnf => ["java", "javac"]
+                .into_iter()
+                .map(|name| {
+                    CommandSpec::new("update-alternatives").args([
+                        "--set",
+                        name,
+                        &format!("{}/bin/{}", java_home, name),
+                    ])
+                })
+                .collect(),
+            PackageManager::Pacman => {
+                vec![CommandSpec::new("archlinux-java").args(["set", &self.get_directory_prefix()])]
+            }
+        }
+    }
+}
+
+impl Tool for Jdk {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        let manager = get_manager(ctx.runner)?;
+        manager.install_package(ctx, &self.get_packages().get())?;
+        if !self.get_options().default.unwrap_or(true) {
+            return Ok(false);
+        }
+        let java_home = self.get_java_home(ctx.runner, manager);
+        for command in self.get_default_commands(manager, &java_home) {
+            let command = manager.sudo(ctx.runner, command);
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+            ctx.runner
+                .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
+        }
+        let shell = shell::get_current();
+        let shell_config = self.get_shell_config(&shell, &java_home);
+        if let Some(legacy) = self.find_legacy_shell_config(&shell, &shell_config) {
+            ctx.ui.info(&format!(
+                "Replacing the JAVA_HOME environment variable in {} config file",
+                shell.name()
+            ));
+            shell.replace_in_config(ctx.ui, &legacy, &shell_config)?;
+            ctx.ui
+                .warning("Make sure to open a new shell before using the JDK");
+        } else if !shell.config_contains_string(&shell_config) {
+            ctx.ui.info(&format!(
+                "Adding JAVA_HOME environment variable to {} config file",
+                shell.name()
+            ));
+            shell.write_to_config(ctx.ui, &shell_config)?;
+            ctx.ui
+                .warning("Make sure to open a new shell before using the JDK");
+        }
+
+        Ok(false)
+    }
+
+    fn print_command(&self, ctx: &Context) {
+        let Ok(manager) = get_manager(ctx.runner) else {
+            return;
+        };
+        for action in manager.plan_package(&self.get_packages().get()) {
+            if let PlannedAction::Command(command) = action {
+                ctx.emit(Event::CommandSpawned { command });
+            }
+        }
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        let Ok(manager) = get_manager(&SystemRunner) else {
+            return vec![PlannedAction::Note(
+                "No package manager found to install the JDK with".to_string(),
+            )];
+        };
+        let mut actions = manager.plan_package(&self.get_packages().get());
+        if self.get_options().default.unwrap_or(true) {
+            let java_home = self.get_known_java_home(manager);
+            for command in self.get_default_commands(manager, &java_home) {
+                actions.push(PlannedAction::Command(
+                    manager.plan_sudo(command).to_string(),
+                ));
+            }
+            let shell = shell::get_current();
+            actions.push(PlannedAction::ShellConfig {
+                path: shell.get_config_path_str(),
+                line: self.get_shell_config(&shell, &java_home),
+            });
+        }
+        actions
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let Some(manager) = detect_manager(runner, env::consts::OS) else {
+            return false;
+        };
+        let packages = self.get_packages();
+        let installed = match manager.get_package(&packages.get()) {
+            Some(name) => manager.is_installed(runner, &[name]),
+            None => runner.has_command("java"),
+        };
+        if !installed || !self.get_options().default.unwrap_or(true) {
+            return installed;
+        }
+        let shell = shell::get_current();
+        let java_home = self.get_java_home(runner, manager);
+        let shell_config = self.get_shell_config(&shell, &java_home);
+        shell.config_contains_string(&shell_config)
+            && self
+                .find_legacy_shell_config(&shell, &shell_config)
+                .is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+
+    #[test]
+    fn uses_homebrew_only_on_macos() {
+        let runner = ScriptedRunner::new();
+        assert_eq!(
+            detect_manager(&runner, "macos"),
+            Some(PackageManager::Homebrew)
+        );
+        assert_eq!(detect_manager(&runner, "linux"), Some(PackageManager::Apt));
+        let runner = ScriptedRunner::new().on("which brew", 1, "", "");
+        assert_eq!(detect_manager(&runner, "macos"), None);
+    }
+
+    #[test]
+    fn resolves_the_vendor_package() {
+        let jdk: Jdk =
+            serde_yaml::from_str("version: 21\nvendor: temurin\ndefault: false").unwrap();
+        let packages = jdk.get_packages();
+        assert_eq!(
+            PackageManager::Apt.get_package(&packages.get()),
+            Some("temurin-21-jdk")
+        );
+        assert_eq!(
+            PackageManager::Homebrew.get_package(&packages.get()),
+            Some("--cask temurin@21")
+        );
+        assert_eq!(PackageManager::Pacman.get_package(&packages.get()), None);
+    }
+
+    #[test]
+    fn replaces_the_old_java11_line() {
+        let jdk = Jdk::java11();
+        let legacy = jdk.get_legacy_shell_configs(&shell::Shell::Fish);
+        assert_eq!(
+            legacy[0],
+            "set -x JAVA_HOME /Library/Java/JavaVirtualMachines/zulu-11.jdk/Contents/Home"
+        );
+        assert!(jdk
+            .get_legacy_shell_configs(&shell::Shell::Zsh)
+            .contains(&"export JAVA_HOME=\"/usr/lib/jvm/java-11-openjdk\"".to_string()));
+    }
+
+    #[test]
+    fn finds_java_home_in_the_alternatives() {
+        let output = "/usr/lib/jvm/java-11-openjdk-amd64/bin/java\n/usr/lib/jvm/temurin-17-jdk-arm64/bin/java\n";
+        let runner = ScriptedRunner::new().on("update-alternatives", 0, output, "");
+        let jdk: Jdk = serde_yaml::from_str("version: 17\nvendor: temurin").unwrap();
+        assert_eq!(
+            jdk.get_java_home(&runner, PackageManager::Apt),
+            "/usr/lib/jvm/temurin-17-jdk-arm64"
+        );
+        let runner = ScriptedRunner::new().on("update-alternatives", 2, "", "");
+        assert_eq!(
+            jdk.get_java_home(&runner, PackageManager::Dnf),
+            "/usr/lib/jvm/temurin-17-jdk"
+        );
+        assert_eq!(
+            Jdk::java11().get_java_home(&runner, PackageManager::Homebrew),
+            "$(/usr/libexec/java_home -v 11)"
+        );
+    }
+}
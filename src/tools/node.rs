@@ -0,0 +1,540 @@
+use std::fmt;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{Error, Result};
+use crate::events::Event;
+use crate::shell;
+
+use super::{
+    package_manager::{PackageManager, SystemPackage},
+    runner::{CommandRunner, CommandSpec},
+    types::{expand_home, Context, PlannedAction, Tool},
+};
+
+const FNM: SystemPackage = SystemPackage {
+    command: "fnm",
+    brew: Some("fnm"),
+    apt: None,
+    dnf: None,
+    pacman: Some("fnm"),
+};
+
+const VOLTA: SystemPackage = SystemPackage {
+    command: "volta",
+    brew: Some("volta"),
+    apt: None,
+    dnf: None,
+    pacman: None,
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeManager {
+    #[default]
+    Fnm,
+    Nvm,
+    Volta,
+}
+
+/// A major version like `20`, a minor one like `"18.17"`, an exact one like
+/// `18.17.1` or an alias like `lts/iron`. Minor versions have to be quoted,
+/// YAML reads `18.10` as the number `18.1`.
+#[derive(Serialize, PartialEq, Debug, Clone)]
+#[serde(transparent)]
+pub struct NodeVersion(String);
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Node {
+    /// Version manager installing node, defaults to fnm
+    #[serde(default)]
+    manager: NodeManager,
+    /// Node versions to install
+    versions: Vec<NodeVersion>,
+    /// Version used by default, defaults to the first of `versions`
+    default: Option<NodeVersion>,
+    /// Enables corepack so `pnpm` and `yarn` are available, defaults to true
+    corepack: Option<bool>,
+}
+
+impl fmt::Display for NodeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Whether `version` is a version number with up to three parts, optionally
+/// prefixed with `v`, or an alias of lowercase letters, digits, `-`, `/` and
+/// `*`
+fn is_valid_version(version: &str) -> bool {
+    let number = version.strip_prefix('v').unwrap_or(version);
+    let parts: Vec<&str> = number.split('.').collect();
+    if parts.len() <= 3
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return true;
+    }
+    version.starts_with(|c: char| c.is_ascii_lowercase())
+        && version
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-/*".contains(c))
+}
+
+struct NodeVersionVisitor;
+
+impl<'de> Visitor<'de> for NodeVersionVisitor {
+    type Value = NodeVersion;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .write_str("a node version like 20, \"18.17\" or 18.17.1, or an alias like lts/iron")
+    }
+
+    fn visit_u64<E: de::Error>(self, major: u64) -> std::result::Result<NodeVersion, E> {
+        Ok(NodeVersion(major.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, version: f64) -> std::result::Result<NodeVersion, E> {
+        Err(E::custom(format!(
+            "node version {} has to be quoted, YAML reads versions like 18.10 as numbers and drops the trailing 0",
+            version
+        )))
+    }
+
+    fn visit_str<E: de::Error>(self, version: &str) -> std::result::Result<NodeVersion, E> {
+        match is_valid_version(version) {
+            true => Ok(NodeVersion(version.to_string())),
+            false => Err(E::invalid_value(de::Unexpected::Str(version), &self)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeVersion {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NodeVersionVisitor)
+    }
+}
+
+impl JsonSchema for NodeVersion {
+    fn schema_name() -> String {
+        "NodeVersion".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        Schema::Object(SchemaObject {
+            instance_type: Some(vec![InstanceType::Integer, InstanceType::String].into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "A major version like `20`, a minor one like `\"18.17\"`, an exact one like `18.17.1` or an alias like `lts/iron`"
+                        .to_string(),
+                ),
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+}
+
+impl NodeManager {
+    fn name(&self) -> &'static str {
+        match self {
+            NodeManager::Fnm => "fnm",
+            NodeManager::Nvm => "nvm",
+            NodeManager::Volta => "volta",
+        }
+    }
+
+    /// Package of the version manager, nvm is only installed with its script
+    fn get_package(&self) -> Option<&'static SystemPackage<'static>> {
+        match self {
+            NodeManager::Fnm => Some(&FNM),
+            NodeManager::Nvm => None,
+            NodeManager::Volta => Some(&VOLTA),
+        }
+    }
+
+    /// Official install script, used when the package manager does not
+    /// have the version manager. The scripts must not touch the shell config.
+    fn get_install_script(&self) -> &'static str {
+        match self {
+            NodeManager::Fnm => "curl -fsSL https://fnm.vercel.app/install | bash -s -- --install-dir \"$HOME/.local/bin\" --skip-shell",
+            NodeManager::Nvm => "curl -fsSL https://raw.githubusercontent.com/nvm-sh/nvm/v0.39.7/install.sh | PROFILE=/dev/null bash",
+            NodeManager::Volta => "curl -fsSL https://get.volta.sh | bash -s -- --skip-setup",
+        }
+    }
+
+    fn get_shell_config(&self, shell: &shell::Shell) -> Result<&'static str> {
+        Ok(match (self, shell) {
+            // The install script puts fnm in ~/.local/bin
+            (NodeManager::Fnm, shell::Shell::Fish) => {
+                "set -gx PATH \"$HOME/.local/bin\" $PATH; fnm env --use-on-cd | source"
+            }
+            (NodeManager::Fnm, _) => {
+                "export PATH=\"$HOME/.local/bin:$PATH\"; eval \"$(fnm env --use-on-cd)\""
+            }
+            (NodeManager::Nvm, shell::Shell::Fish) => {
+                return Err(Error::Tool(
+                    "nvm does not support fish, use fnm or volta instead".to_string(),
+                ))
+            }
+            (NodeManager::Nvm, _) => {
+                "export NVM_DIR=\"$HOME/.nvm\"; [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\""
+            }
+            (NodeManager::Volta, shell::Shell::Fish) => {
+                "set -gx VOLTA_HOME \"$HOME/.volta\"; set -gx PATH \"$VOLTA_HOME/bin\" $PATH"
+            }
+            (NodeManager::Volta, _) => {
+                "export VOLTA_HOME=\"$HOME/.volta\"; export PATH=\"$VOLTA_HOME/bin:$PATH\""
+            }
+        })
+    }
+
+    /// Runs a version manager command. nvm is a shell function and has to be
+    /// loaded first.
+    fn command(&self, args: &[&str]) -> CommandSpec {
+        match self {
+            NodeManager::Nvm => CommandSpec::new("bash").args([
+                "-c",
+                ". \"${NVM_DIR:-$HOME/.nvm}/nvm.sh\" && nvm \"$@\"",
+                "nvm",
+            ]),
+            _ => CommandSpec::new(self.name()),
+        }
+        .args(args)
+    }
+}
+
+impl Node {
+    fn get_default(&self) -> Option<String> {
+        self.default
+            .as_ref()
+            .or(self.versions.first())
+            .map(|v| v.to_string())
+    }
+
+    fn get_install_commands(&self) -> Vec<CommandSpec> {
+        let versions = self.versions.iter().map(|v| v.to_string());
+        match self.manager {
+            NodeManager::Fnm | NodeManager::Nvm => versions
+                .map(|v| self.manager.command(&["install", &v]))
+                .collect(),
+            // `volta install` also makes the version the default
+            NodeManager::Volta => versions
+                .map(|v| self.manager.command(&["fetch", &format!("node@{}", v)]))
+                .collect(),
+        }
+    }
+
+    fn get_default_commands(&self) -> Vec<CommandSpec> {
+        let Some(default) = self.get_default() else {
+            return Vec::new();
+        };
+        let mut commands = vec![match self.manager {
+            NodeManager::Fnm => self.manager.command(&["default", &default]),
+            NodeManager::Nvm => self.manager.command(&["alias", "default", &default]),
+            NodeManager::Volta => self
+                .manager
+                .command(&["install", &format!("node@{}", default)]),
+        }];
+        if self.corepack.unwrap_or(true) {
+            commands.push(match self.manager {
+                NodeManager::Fnm => self
+                    .manager
+                    .command(&["exec", "--using", &default, "corepack", "enable"]),
+                NodeManager::Nvm => self
+                    .manager
+                    .command(&["exec", &default, "corepack", "enable"]),
+                // Volta manages its own shims, corepack has to put its shims
+                // next to them
+                NodeManager::Volta => CommandSpec::new("sh").args([
+                    "-c",
+                    "volta install corepack && corepack enable --install-directory \"$1\"",
+                    "sh",
+                    &expand_home("~/.volta/bin"),
+                ]),
+            });
+        }
+        commands
+    }
+
+    /// Command that succeeds when corepack's `pnpm` shim is next to the
+    /// `default` node
+    fn get_corepack_check(&self, default: &str) -> CommandSpec {
+        let script = "test -e \"$(dirname \"$(command -v node)\")/pnpm\"";
+        match self.manager {
+            NodeManager::Fnm => self
+                .manager
+                .command(&["exec", "--using", default, "sh", "-c", script]),
+            NodeManager::Nvm => self.manager.command(&["exec", default, "sh", "-c", script]),
+            NodeManager::Volta => {
+                CommandSpec::new("test").args(["-e", &expand_home("~/.volta/bin/pnpm")])
+            }
+        }
+    }
+
+    /// Whether `versions`, what the manager lists as installed, has every
+    /// version and the default one, and corepack is enabled if it should be
+    fn is_set_up(&self, runner: &dyn CommandRunner, versions: &str) -> bool {
+        if !self
+            .versions
+            .iter()
+            .all(|v| is_installed(versions, &v.to_string()))
+        {
+            return false;
+        }
+        let Some(default) = self.get_default() else {
+            return true;
+        };
+        // Every manager marks the default version with "default"
+        let is_default = versions
+            .lines()
+            .filter(|line| line.contains("default"))
+            .any(|line| is_installed(line, &default));
+        is_default
+            && (!self.corepack.unwrap_or(true)
+                || runner
+                    .get_output(&self.get_corepack_check(&default))
+                    .is_some())
+    }
+
+    fn get_commands(&self) -> Vec<CommandSpec> {
+        [self.get_install_commands(), self.get_default_commands()].concat()
+    }
+
+    /// Installs the version manager unless it is already there, preferring
+    /// the package manager of the machine over the official install script.
+    /// Returns whether it was installed.
+    fn install_manager(&self, ctx: &Context) -> Result<bool> {
+        if ctx
+            .runner
+            .get_output(&self.manager.command(&["--version"]))
+            .is_some()
+        {
+            return Ok(false);
+        }
+        if let Some(package) = self.manager.get_package() {
+            if let Some(manager) = PackageManager::detect(ctx.runner) {
+                if manager.get_package(package).is_some() {
+                    manager.install_package(ctx, package)?;
+                    return Ok(true);
+                }
+            }
+        }
+        let command = CommandSpec::new("sh").args(["-c", self.manager.get_install_script()]);
+        ctx.emit(Event::CommandSpawned {
+            command: command.to_string(),
+        });
+        ctx.runner
+            .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
+        Ok(true)
+    }
+
+    fn plan_manager(&self) -> Vec<PlannedAction> {
+        let manager = PackageManager::current();
+        match self.manager.get_package() {
+            Some(package) if manager.get_package(package).is_some() => {
+                manager.plan_package(package)
+            }
+            _ => vec![PlannedAction::Command(
+                CommandSpec::new("sh")
+                    .args(["-c", self.manager.get_install_script()])
+                    .to_string(),
+            )],
+        }
+    }
+}
+
+/// Whether one of the installed `versions`, e.g. `v20.5.1` or `node@20.5.1`,
+/// is `version`. Aliases like `lts/iron` never match.
+fn is_installed(versions: &str, version: &str) -> bool {
+    versions
+        .split_whitespace()
+        .map(|word| word.trim_start_matches("node@").trim_start_matches('v'))
+        .any(|installed| {
+            installed == version
+                || installed
+                    .strip_prefix(version)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+}
+
+impl Tool for Node {
+    fn install(&self, ctx: &Context, tool_step: usize) -> Result<bool> {
+        let shell = shell::get_current();
+        let shell_config = self.manager.get_shell_config(&shell)?;
+        if tool_step == 0 {
+            let installed = self.install_manager(ctx)?;
+            let configured = shell.config_contains_string(shell_config);
+            if !configured {
+                ctx.ui.info(&format!(
+                    "Adding {} config to {} config file",
+                    self.manager.name(),
+                    shell.name()
+                ));
+                shell.write_to_config(ctx.ui, shell_config)?;
+            }
+            if installed || !configured {
+                ctx.ui.warning(
+                    "Open a new shell and run this command again to complete installation",
+                );
+                return Ok(true);
+            }
+        }
+
+        for command in self.get_commands() {
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+            ctx.runner
+                .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
+        }
+
+        Ok(false)
+    }
+
+    fn print_command(&self, ctx: &Context) {
+        for command in self.get_commands() {
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+        }
+    }
+
+    fn plan(&self, tool_step: usize) -> Vec<PlannedAction> {
+        let shell = shell::get_current();
+        let mut actions = Vec::new();
+        if tool_step == 0 {
+            actions.append(&mut self.plan_manager());
+            match self.manager.get_shell_config(&shell) {
+                Ok(line) => actions.push(PlannedAction::ShellConfig {
+                    path: shell.get_config_path_str(),
+                    line: line.to_string(),
+                }),
+                Err(e) => actions.push(PlannedAction::Note(e.to_string())),
+            }
+            actions.push(PlannedAction::Pause(
+                "Open a new shell and run this command again to complete installation".to_string(),
+            ));
+        }
+        actions.extend(
+            self.get_commands()
+                .into_iter()
+                .map(|command| PlannedAction::Command(command.to_string())),
+        );
+        actions
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let shell = shell::get_current();
+        match self.manager.get_shell_config(&shell) {
+            Ok(line) if shell.config_contains_string(line) => {}
+            _ => return false,
+        }
+        let list = match self.manager {
+            NodeManager::Fnm => self.manager.command(&["list"]),
+            NodeManager::Nvm => self.manager.command(&["ls", "--no-colors"]),
+            NodeManager::Volta => self.manager.command(&["list", "node", "--format", "plain"]),
+        };
+        runner
+            .get_output(&list)
+            .is_some_and(|versions| self.is_set_up(runner, &versions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn installs_versions_and_enables_corepack() {
+        let runner = ScriptedRunner::new();
+        let node: Node = serde_yaml::from_str("versions: [20, '18.17', 18.10.0]").unwrap();
+        assert!(!node.install(&Context::new(&runner, &SilentUi), 1).unwrap());
+        assert_eq!(
+            runner.argvs(),
+            vec![
+                vec!["fnm", "install", "20"],
+                vec!["fnm", "install", "18.17"],
+                vec!["fnm", "install", "18.10.0"],
+                vec!["fnm", "default", "20"],
+                vec!["fnm", "exec", "--using", "20", "corepack", "enable"],
+            ]
+        );
+    }
+
+    #[test]
+    fn installs_the_manager_only_when_missing() {
+        let node: Node = serde_yaml::from_str("versions: [20]").unwrap();
+        let runner = ScriptedRunner::new().on("fnm --version", 0, "fnm 1.35.1\n", "");
+        assert!(!node
+            .install_manager(&Context::new(&runner, &SilentUi))
+            .unwrap());
+        assert_eq!(runner.argvs(), vec![vec!["fnm", "--version"]]);
+        let runner = ScriptedRunner::new()
+            .missing("fnm")
+            .missing("which brew")
+            .on("which apt-get", 0, "/usr/bin/apt-get\n", "");
+        assert!(node
+            .install_manager(&Context::new(&runner, &SilentUi))
+            .unwrap());
+        let argvs = runner.argvs();
+        assert_eq!(argvs.last().unwrap()[0], "sh");
+    }
+
+    #[test]
+    fn loads_nvm_before_running_it() {
+        let node: Node = serde_yaml::from_str(
+            "manager: nvm\nversions: ['20']\ndefault: lts/iron\ncorepack: false",
+        )
+        .unwrap();
+        let commands = node.get_commands();
+        let argvs: Vec<Vec<&str>> = commands.iter().map(|c| c.argv()).collect();
+        assert_eq!(argvs[0][4..], ["install", "20"]);
+        assert_eq!(argvs[1][4..], ["alias", "default", "lts/iron"]);
+        assert_eq!(argvs.len(), 2);
+    }
+
+    #[test]
+    fn rejects_unquoted_minor_versions() {
+        let error = serde_yaml::from_str::<Node>("versions: [18.10]").unwrap_err();
+        assert!(error.to_string().contains("has to be quoted"));
+        assert!(serde_yaml::from_str::<Node>("versions: ['18 17']").is_err());
+        let node: Node = serde_yaml::from_str("versions: ['18.10', lts/iron, v20]").unwrap();
+        assert_eq!(node.get_default(), Some("18.10".to_string()));
+    }
+
+    #[test]
+    fn checks_the_default_version_and_corepack() {
+        let list = "* v18.17.1\n* v20.5.1 default\n";
+        let node: Node = serde_yaml::from_str("versions: [18, 20]").unwrap();
+        assert!(!node.is_set_up(&ScriptedRunner::new(), list));
+        let node: Node = serde_yaml::from_str("versions: [18, 20]\ndefault: 20").unwrap();
+        let runner = ScriptedRunner::new().on("fnm exec", 1, "", "");
+        assert!(!node.is_set_up(&runner, list));
+        let runner = ScriptedRunner::new();
+        assert!(node.is_set_up(&runner, list));
+        assert_eq!(runner.argvs()[0][..4], ["fnm", "exec", "--using", "20"]);
+    }
+
+    #[test]
+    fn matches_installed_versions() {
+        let fnm = "* v18.17.1\n* v20.5.1 default\n* system\n";
+        assert!(is_installed(fnm, "20"));
+        assert!(is_installed(fnm, "18.17"));
+        assert!(!is_installed(fnm, "2"));
+        assert!(!is_installed(fnm, "lts/iron"));
+        assert!(is_installed("runtime node@20.5.1 (default)\n", "20.5.1"));
+    }
+}
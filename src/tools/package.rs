@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::events::Event;
+
+use super::{
+    package_manager::PackageManager,
+    runner::{CommandRunner, CommandSpec},
+    types::{Context, PlannedAction, Tool},
+};
+
+/// Names of a package where they differ from its logical name
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PackageNames {
+    brew: Option<String>,
+    apt: Option<String>,
+    dnf: Option<String>,
+    pacman: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum PackageEntry {
+    /// A package with the same name in every package manager
+    Name(String),
+    /// A logical name mapped to the names of the package managers, e.g.
+    /// `fd: { apt: fd-find }`
+    Mapped(BTreeMap<String, PackageNames>),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Package {
+    /// Space separated list of packages with the same name everywhere
+    Packages(String),
+    List(Vec<PackageEntry>),
+}
+
+impl PackageNames {
+    fn get(&self, manager: PackageManager) -> Option<&str> {
+        match manager {
+            PackageManager::Homebrew => self.brew.as_deref(),
+            PackageManager::Apt => self.apt.as_deref(),
+            PackageManager::Dnf => self.dnf.as_deref(),
+            PackageManager::Pacman => self.pacman.as_deref(),
+        }
+    }
+}
+
+impl Package {
+    /// Resolves the logical names to the package names of `manager`
+    fn get_packages(&self, manager: PackageManager) -> Vec<String> {
+        match self {
+            Package::Packages(s) => s.split_whitespace().map(|p| p.to_string()).collect(),
+            Package::List(entries) => entries
+                .iter()
+                .flat_map(|entry| match entry {
+                    PackageEntry::Name(name) => vec![name.clone()],
+                    PackageEntry::Mapped(names) => names
+                        .iter()
+                        .map(|(name, names)| names.get(manager).unwrap_or(name).to_string())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Install commands for `manager`, without `sudo`
+    fn get_commands(&self, manager: PackageManager) -> Vec<CommandSpec> {
+        let packages = self.get_packages(manager);
+        let packages: Vec<&str> = packages.iter().map(|p| p.as_str()).collect();
+        manager.install_commands(&packages)
+    }
+}
+
+impl Tool for Package {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        let manager = PackageManager::get(ctx.runner)?;
+        for command in self.get_commands(manager) {
+            let command = manager.sudo(ctx.runner, command);
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+            ctx.runner
+                .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
+        }
+
+        Ok(false)
+    }
+
+    fn print_command(&self, ctx: &Context) {
+        let manager = PackageManager::detect(ctx.runner).unwrap_or(PackageManager::Homebrew);
+        for command in self.get_commands(manager) {
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+        }
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        let manager = PackageManager::current();
+        self.get_commands(manager)
+            .into_iter()
+            .map(|command| PlannedAction::Command(manager.plan_sudo(command).to_string()))
+            .collect()
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let Some(manager) = PackageManager::detect(runner) else {
+            return false;
+        };
+        let packages = self.get_packages(manager);
+        let packages: Vec<&str> = packages.iter().map(|p| p.as_str()).collect();
+        manager.is_installed(runner, &packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn resolves_names_for_each_manager() {
+        let package: Package = serde_yaml::from_str(
+            "- ripgrep\n- fd: { apt: fd-find }\n- java: { brew: --cask temurin, apt: default-jdk }\n",
+        )
+        .unwrap();
+        let argvs = |manager| -> Vec<Vec<String>> {
+            package
+                .get_commands(manager)
+                .iter()
+                .map(|c| c.argv().iter().map(|a| a.to_string()).collect())
+                .collect()
+        };
+        assert_eq!(
+            argvs(PackageManager::Homebrew),
+            vec![
+                vec!["brew", "install", "ripgrep", "fd"],
+                vec!["brew", "install", "--cask", "temurin"],
+            ]
+        );
+        assert_eq!(
+            argvs(PackageManager::Apt),
+            vec![vec![
+                "apt-get",
+                "install",
+                "-y",
+                "ripgrep",
+                "fd-find",
+                "default-jdk"
+            ]]
+        );
+    }
+
+    #[test]
+    fn installs_with_the_detected_manager() {
+        let runner = ScriptedRunner::new()
+            .on("which brew", 1, "", "")
+            .on("id -u", 0, "0\n", "");
+        let package: Package = serde_yaml::from_str("- fd: { apt: fd-find }\n- jq\n").unwrap();
+        package
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        assert_eq!(
+            runner.argvs().last().unwrap(),
+            &vec!["apt-get", "install", "-y", "fd-find", "jq"]
+        );
+    }
+}
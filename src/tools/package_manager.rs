@@ -0,0 +1,277 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::events::Event;
+
+use super::{
+    homebrew::{get_install_commands, BrewPackage},
+    runner::{CommandRunner, CommandSpec, SystemRunner},
+    types::{Context, PlannedAction},
+};
+
+/// A system package manager tools can install their dependencies with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PackageManager {
+    Homebrew,
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+/// Names of a package in every package manager, None where it is not
+/// packaged. Homebrew names may include flags, e.g. `--cask zulu11`.
+pub struct SystemPackage<'a> {
+    /// Command the package provides, used when it cannot be installed with
+    /// the host's package manager
+    pub command: &'a str,
+    pub brew: Option<&'a str>,
+    pub apt: Option<&'a str>,
+    pub dnf: Option<&'a str>,
+    pub pacman: Option<&'a str>,
+}
+
+impl PackageManager {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Homebrew => "brew",
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            PackageManager::Homebrew => "brew",
+            PackageManager::Apt => "apt-get",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+        }
+    }
+
+    /// Finds the package manager of this machine. Homebrew is preferred when
+    /// it is installed, also on Linux, then apt, dnf and pacman.
+    pub fn detect(runner: &dyn CommandRunner) -> Option<Self> {
+        match runner.has_command(PackageManager::Homebrew.program()) {
+            true => Some(PackageManager::Homebrew),
+            false => Self::detect_native(runner),
+        }
+    }
+
+    /// Finds the package manager of the Linux distribution, ignoring Homebrew
+    pub fn detect_native(runner: &dyn CommandRunner) -> Option<Self> {
+        [
+            PackageManager::Apt,
+            PackageManager::Dnf,
+            PackageManager::Pacman,
+        ]
+        .into_iter()
+        .find(|manager| runner.has_command(manager.program()))
+    }
+
+    /// The package manager of the machine siu runs on, Homebrew if there is
+    /// none. Used for plans, which do not get a runner.
+    pub fn current() -> Self {
+        Self::detect(&SystemRunner).unwrap_or(PackageManager::Homebrew)
+    }
+
+    /// Like `detect`, but fails with a helpful message when there is none
+    pub fn get(runner: &dyn CommandRunner) -> Result<Self> {
+        Self::detect(runner).ok_or_else(|| {
+            Error::MissingCommand("a package manager (brew, apt-get, dnf or pacman)".to_string())
+        })
+    }
+
+    /// Runs a command as root: through `sudo`, unless siu already runs as
+    /// root. Homebrew refuses to run as root and never needs it.
+    pub fn sudo(&self, runner: &dyn CommandRunner, command: CommandSpec) -> CommandSpec {
+        if *self == PackageManager::Homebrew {
+            return command;
+        }
+        let is_root = runner
+            .get_output(&CommandSpec::new("id").arg("-u"))
+            .is_some_and(|uid| uid.trim() == "0");
+        if is_root {
+            return command;
+        }
+        CommandSpec::new("sudo").args(command.argv())
+    }
+
+    /// `sudo` as a plan shows it, checked against the machine siu runs on
+    pub fn plan_sudo(&self, command: CommandSpec) -> CommandSpec {
+        self.sudo(&SystemRunner, command)
+    }
+
+    /// Command installing `packages`, without `sudo`
+    pub fn install_command(&self, packages: &[&str]) -> CommandSpec {
+        let command = match self {
+            PackageManager::Homebrew => CommandSpec::new("brew").arg("install"),
+            PackageManager::Apt => CommandSpec::new("apt-get").args(["install", "-y"]),
+            PackageManager::Dnf => CommandSpec::new("dnf").args(["install", "-y"]),
+            PackageManager::Pacman => {
+                CommandSpec::new("pacman").args(["-S", "--needed", "--noconfirm"])
+            }
+        };
+        command.args(packages.iter().flat_map(|p| p.split_whitespace()))
+    }
+
+    /// Commands installing `packages`, without `sudo`. Homebrew packages
+    /// with flags, e.g. `--cask temurin`, get a command of their own so the
+    /// flags do not apply to the others.
+    pub fn install_commands(&self, packages: &[&str]) -> Vec<CommandSpec> {
+        match self {
+            PackageManager::Homebrew => {
+                let packages: Vec<BrewPackage> = packages
+                    .iter()
+                    .flat_map(|p| BrewPackage::parse(p))
+                    .collect();
+                get_install_commands(&packages, false)
+            }
+            _ => vec![self.install_command(packages)],
+        }
+    }
+
+    /// Whether every package is installed
+    pub fn is_installed(&self, runner: &dyn CommandRunner, packages: &[&str]) -> bool {
+        let packages: Vec<&str> = packages
+            .iter()
+            .flat_map(|p| p.split_whitespace())
+            .filter(|p| !p.starts_with('-'))
+            .collect();
+        match self {
+            PackageManager::Homebrew => runner
+                .get_output(
+                    &CommandSpec::new("brew")
+                        .args(["list", "--versions"])
+                        .args(&packages),
+                )
+                .is_some(),
+            PackageManager::Apt => {
+                let query = CommandSpec::new("dpkg-query")
+                    .args(["-W", "-f=${db:Status-Status}\\n"])
+                    .args(&packages);
+                runner.get_output(&query).is_some_and(|output| {
+                    output.lines().filter(|l| l.trim() == "installed").count() == packages.len()
+                })
+            }
+            PackageManager::Dnf => runner
+                .get_output(&CommandSpec::new("rpm").arg("-q").args(&packages))
+                .is_some(),
+            PackageManager::Pacman => runner
+                .get_output(&CommandSpec::new("pacman").arg("-Q").args(&packages))
+                .is_some(),
+        }
+    }
+
+    /// Name of `package` in this package manager
+    pub fn get_package<'a>(&self, package: &SystemPackage<'a>) -> Option<&'a str> {
+        match self {
+            PackageManager::Homebrew => package.brew,
+            PackageManager::Apt => package.apt,
+            PackageManager::Dnf => package.dnf,
+            PackageManager::Pacman => package.pacman,
+        }
+    }
+
+    /// Installs a package a tool depends on. If the package manager does not
+    /// have it, the command it provides must already be installed.
+    pub fn install_package(&self, ctx: &Context, package: &SystemPackage) -> Result<()> {
+        let Some(name) = self.get_package(package) else {
+            if ctx.runner.has_command(package.command) {
+                return Ok(());
+            }
+            return Err(Error::Tool(format!(
+                "{} cannot be installed with {}, install it yourself and run siu again",
+                package.command,
+                self.name()
+            )));
+        };
+        let command = self.sudo(ctx.runner, self.install_command(&[name]));
+        ctx.emit(Event::CommandSpawned {
+            command: command.to_string(),
+        });
+        ctx.runner
+            .run_checked(&command, &|stream, line| ctx.output(stream, line))
+    }
+
+    pub fn plan_package(&self, package: &SystemPackage) -> Vec<PlannedAction> {
+        match self.get_package(package) {
+            Some(name) => vec![PlannedAction::Command(
+                self.plan_sudo(self.install_command(&[name])).to_string(),
+            )],
+            None => vec![PlannedAction::Note(format!(
+                "{} must already be installed",
+                package.command
+            ))],
+        }
+    }
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    const GIT: SystemPackage = SystemPackage {
+        command: "git",
+        brew: Some("git"),
+        apt: Some("git"),
+        dnf: Some("git"),
+        pacman: None,
+    };
+
+    #[test]
+    fn prefers_homebrew_then_apt() {
+        let runner = ScriptedRunner::new();
+        assert_eq!(
+            PackageManager::detect(&runner),
+            Some(PackageManager::Homebrew)
+        );
+        let runner = ScriptedRunner::new().on("which brew", 1, "", "");
+        assert_eq!(PackageManager::detect(&runner), Some(PackageManager::Apt));
+    }
+
+    #[test]
+    fn installs_with_sudo_unless_root() {
+        let runner = ScriptedRunner::new().on("id -u", 0, "1000\n", "");
+        PackageManager::Apt
+            .install_package(&Context::new(&runner, &SilentUi), &GIT)
+            .unwrap();
+        assert_eq!(
+            runner.argvs()[1],
+            vec!["sudo", "apt-get", "install", "-y", "git"]
+        );
+        let runner = ScriptedRunner::new().on("id -u", 0, "0\n", "");
+        PackageManager::Dnf
+            .install_package(&Context::new(&runner, &SilentUi), &GIT)
+            .unwrap();
+        assert_eq!(runner.argvs()[1], vec!["dnf", "install", "-y", "git"]);
+    }
+
+    #[test]
+    fn requires_unpackaged_commands() {
+        let missing = ScriptedRunner::new().on("which git", 1, "", "");
+        assert!(PackageManager::Pacman
+            .install_package(&Context::new(&missing, &SilentUi), &GIT)
+            .is_err());
+        let runner = ScriptedRunner::new();
+        assert!(PackageManager::Pacman
+            .install_package(&Context::new(&runner, &SilentUi), &GIT)
+            .is_ok());
+    }
+
+    #[test]
+    fn checks_dpkg_status() {
+        let runner = ScriptedRunner::new().on("dpkg-query", 0, "installed\nnot-installed\n", "");
+        assert!(!PackageManager::Apt.is_installed(&runner, &["git", "curl"]));
+        let runner = ScriptedRunner::new().on("dpkg-query", 0, "installed\ninstalled\n", "");
+        assert!(PackageManager::Apt.is_installed(&runner, &["git", "curl"]));
+    }
+}
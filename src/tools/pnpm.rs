@@ -1,12 +1,20 @@
-use owo_colors::OwoColorize;
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 
-use super::types::Tool;
+use crate::error::Result;
+use crate::events::Event;
+
+use super::{
+    runner::{CommandRunner, CommandSpec},
+    types::{get_package_name, is_version_of, Context, PlannedAction, Tool},
+};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub enum Pnpm {
+    /// Space separated list of packages passed to `pnpm install --global`
     Packages(String),
 }
 
@@ -15,29 +23,119 @@ impl Pnpm {
         let Pnpm::Packages(s) = self;
         s.split(' ').collect::<Vec<&str>>()
     }
+
+    fn get_command(&self) -> String {
+        let Pnpm::Packages(s) = self;
+        format!("pnpm install --global {}", s)
+    }
+}
+
+/// Versions of the global packages in the output of `pnpm list --json`, by
+/// name
+fn get_installed_versions(output: &str) -> BTreeMap<String, String> {
+    let Ok(serde_json::Value::Array(projects)) = serde_json::from_str(output) else {
+        return BTreeMap::new();
+    };
+    projects
+        .iter()
+        .filter_map(|project| project.get("dependencies")?.as_object())
+        .flatten()
+        .filter_map(|(name, dependency)| {
+            let version = dependency.get("version")?.as_str()?;
+            Some((name.clone(), version.to_string()))
+        })
+        .collect()
 }
 
 impl Tool for Pnpm {
-    fn install(&self, _: usize) -> Result<bool, String> {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
         let args = [
             Vec::from(["install", "--global"]),
             self.get_packages().to_owned(),
         ]
         .concat();
-        let mut child = Command::new("pnpm").args(args).spawn().unwrap();
-        match child.wait() {
-            Ok(status) => {
-                if !status.success() {
-                    return Err("Failed to install pnpm global packages".to_string());
-                }
-            }
-            Err(e) => return Err(format!("Failed to run command: {}", e)),
-        }
+        self.print_command(ctx);
+        ctx.runner
+            .run_checked(&CommandSpec::new("pnpm").args(args), &|stream, line| {
+                ctx.output(stream, line)
+            })?;
 
         Ok(false)
     }
-    fn print_command(&self) {
-        let Pnpm::Packages(s) = self;
-        println!("\n{}\n", format!("pnpm install --global {}", s).italic());
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self.get_command(),
+        });
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        vec![PlannedAction::Command(self.get_command())]
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let list = CommandSpec::new("pnpm").args(["list", "--global", "--json", "--depth", "0"]);
+        let Some(output) = runner.get_output(&list) else {
+            return false;
+        };
+        let installed = get_installed_versions(&output);
+        self.get_packages().iter().all(|p| {
+            installed
+                .get(get_package_name(p))
+                .is_some_and(|version| is_version_of(p, version))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn installs_globally() {
+        let runner = ScriptedRunner::new();
+        let pnpm = Pnpm::Packages(String::from("typescript@5.1.3 eslint"));
+        pnpm.install(&Context::new(&runner, &SilentUi), 0).unwrap();
+        assert_eq!(
+            runner.argvs(),
+            vec![vec![
+                "pnpm",
+                "install",
+                "--global",
+                "typescript@5.1.3",
+                "eslint"
+            ]]
+        );
+    }
+
+    fn list(dependencies: &str) -> String {
+        format!(
+            "[{{\"path\": \"/pnpm/global/5\", \"dependencies\": {{{}}}}}]",
+            dependencies
+        )
+    }
+
+    #[test]
+    fn checks_the_global_packages() {
+        let pnpm = Pnpm::Packages(String::from("typescript@5.1.3 @scope/pkg"));
+        let output = list(
+            "\"typescript\": {\"version\": \"5.1.3\"}, \"@scope/pkg\": {\"version\": \"1.0.0\"}",
+        );
+        let runner = ScriptedRunner::new().on("pnpm list", 0, &output, "");
+        assert!(pnpm.check(&runner));
+        let output = list("\"typescript\": {\"version\": \"5.1.3\"}");
+        let runner = ScriptedRunner::new().on("pnpm list", 0, &output, "");
+        assert!(!pnpm.check(&runner));
+    }
+
+    #[test]
+    fn checks_the_pinned_version() {
+        let pnpm = Pnpm::Packages(String::from("typescript@5.1.3"));
+        let output = list("\"typescript\": {\"version\": \"5.0.4\"}");
+        let runner = ScriptedRunner::new().on("pnpm list", 0, &output, "");
+        assert!(!pnpm.check(&runner));
+        let pnpm = Pnpm::Packages(String::from("typescript@5"));
+        assert!(pnpm.check(&runner));
     }
 }
@@ -1,98 +1,222 @@
-use owo_colors::OwoColorize;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 
+use crate::error::Result;
+use crate::events::Event;
 use crate::shell;
 
-use super::{homebrew::Homebrew, types::Tool};
+use super::{
+    package_manager::{PackageManager, SystemPackage},
+    runner::{CommandRunner, CommandSpec},
+    types::{Context, PlannedAction, Tool},
+};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+const RBENV: SystemPackage = SystemPackage {
+    command: "rbenv",
+    brew: Some("rbenv"),
+    apt: Some("rbenv"),
+    dnf: Some("rbenv"),
+    pacman: None,
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Rbenv {
+    /// Installs rbenv with the system package manager and adds its init line to your shell config
     install: Option<bool>,
+    /// Ruby version passed to `rbenv install`
     ruby_version: String,
+    /// Sets the installed version as the global ruby version
     global: Option<bool>,
 }
 
+impl Rbenv {
+    fn get_shell_config(&self, shell: &shell::Shell) -> &'static str {
+        match shell {
+            shell::Shell::Bash => "eval \"$(rbenv init - bash)\"",
+            shell::Shell::Zsh => "eval \"$(rbenv init - zsh)\"",
+            shell::Shell::Fish => "status --is-interactive; and rbenv init - fish | source",
+        }
+    }
+
+    fn get_command(&self) -> String {
+        format!("rbenv install {}", self.ruby_version)
+    }
+}
+
 impl Tool for Rbenv {
-    fn install(&self, tool_step: usize) -> Result<bool, String> {
-        let shell = shell::get_current().expect("Failed to get current shell");
+    fn install(&self, ctx: &Context, tool_step: usize) -> Result<bool> {
+        let shell = shell::get_current();
         if let Some(install) = self.install {
             if install && tool_step == 0 {
-                let brew = Homebrew::Packages(String::from("rbenv"));
-                brew.install(tool_step)?;
-                let rbenv_shell_config = match shell {
-                    shell::Shell::Bash => "eval \"$(rbenv init - bash)\"",
-                    shell::Shell::Zsh => "eval \"$(rbenv init - zsh)\"",
-                    shell::Shell::Fish => "status --is-interactive; and rbenv init - fish | source",
-                };
+                PackageManager::get(ctx.runner)?.install_package(ctx, &RBENV)?;
+                let rbenv_shell_config = self.get_shell_config(&shell);
                 if !shell.config_contains_string(rbenv_shell_config) {
-                    println!("Adding rbenv config to {} config file", shell.name());
-                    if let Err(e) = shell.write_to_config(rbenv_shell_config) {
-                        return Err(format!(
-                            "Failed to write rbenv config to {} config: {}",
-                            shell.name(),
-                            e
-                        ));
-                    }
-                    println!(
-                        "\n{}",
-                        "Open a new shell and run this command again to complete installation"
-                            .purple()
-                            .bold()
+                    ctx.ui.info(&format!(
+                        "Adding rbenv config to {} config file",
+                        shell.name()
+                    ));
+                    shell.write_to_config(ctx.ui, rbenv_shell_config)?;
+                    ctx.ui.warning(
+                        "Open a new shell and run this command again to complete installation",
                     );
                     return Ok(true);
                 }
             }
         }
 
-        if !shell.has_command("rbenv") {
-            return Err(
-                "rbenv not found, make sure you completed the previous step before continuing"
-                    .to_string(),
-            );
-        }
-
-        self.print_command();
-        let child = Command::new("rbenv")
-            .args(["install", &self.ruby_version])
-            .spawn()
-            .unwrap();
-        let output = child.wait_with_output().expect("Failed to run command");
-        if !output.status.success() {
-            let err_output = String::from_utf8(output.stderr).unwrap();
-            // Rbenv handles aborting when a ruby version is already installed with
-            // an exit code of 1, but it is not an error.
-            if !err_output.is_empty() {
-                return Err("Failed to install ruby version".to_string());
-            }
+        self.print_command(ctx);
+        let install = CommandSpec::new("rbenv").args(["install", &self.ruby_version]);
+        let result = ctx.run(&install)?;
+        // Rbenv handles aborting when a ruby version is already installed with
+        // an exit code of 1, but it is not an error.
+        if !result.success() && !result.stderr.contains("already exists") {
+            return Err(result.into_error());
         }
         if let Some(global) = self.global {
             if global {
-                println!(
-                    "\n{}\n",
-                    format!("rbenv global {}", self.ruby_version).italic()
-                );
-                let mut child = Command::new("rbenv")
-                    .args(["global", &self.ruby_version])
-                    .spawn()
-                    .unwrap();
-                match child.wait() {
-                    Ok(status) => {
-                        if !status.success() {
-                            return Err("Failed to set global ruby version".to_string());
-                        }
-                    }
-                    Err(e) => return Err(format!("Failed to run command: {}", e)),
-                }
+                ctx.emit(Event::CommandSpawned {
+                    command: format!("rbenv global {}", self.ruby_version),
+                });
+                ctx.runner.run_checked(
+                    &CommandSpec::new("rbenv").args(["global", &self.ruby_version]),
+                    &|stream, line| ctx.output(stream, line),
+                )?;
             }
         }
 
         Ok(false)
     }
-    fn print_command(&self) {
-        println!(
-            "\n{}\n",
-            format!("rbenv install {}", self.ruby_version).italic()
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self.get_command(),
+        });
+    }
+
+    fn plan(&self, tool_step: usize) -> Vec<PlannedAction> {
+        let shell = shell::get_current();
+        let mut actions = Vec::new();
+        if self.install.unwrap_or(false) && tool_step == 0 {
+            actions.append(&mut PackageManager::current().plan_package(&RBENV));
+            actions.push(PlannedAction::ShellConfig {
+                path: shell.get_config_path_str(),
+                line: self.get_shell_config(&shell).to_string(),
+            });
+            actions.push(PlannedAction::Pause(
+                "Open a new shell and run this command again to complete installation".to_string(),
+            ));
+        }
+        actions.push(PlannedAction::Command(self.get_command()));
+        if self.global.unwrap_or(false) {
+            actions.push(PlannedAction::Command(format!(
+                "rbenv global {}",
+                self.ruby_version
+            )));
+        }
+        actions
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let shell = shell::get_current();
+        if self.install.unwrap_or(false)
+            && !(shell.config_exists()
+                && shell.config_contains_string(self.get_shell_config(&shell)))
+        {
+            return false;
+        }
+        let Some(versions) =
+            runner.get_output(&CommandSpec::new("rbenv").args(["versions", "--bare"]))
+        else {
+            return false;
+        };
+        if !versions.lines().any(|v| v.trim() == self.ruby_version) {
+            return false;
+        }
+        if self.global.unwrap_or(false) {
+            return runner
+                .get_output(&CommandSpec::new("rbenv").arg("global"))
+                .map(|v| v.trim() == self.ruby_version)
+                .unwrap_or(false);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    fn rbenv(global: bool) -> Rbenv {
+        Rbenv {
+            install: None,
+            ruby_version: String::from("3.2.2"),
+            global: Some(global),
+        }
+    }
+
+    #[test]
+    fn installs_and_sets_the_global_version() {
+        let runner = ScriptedRunner::new();
+        assert!(!rbenv(true)
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap());
+        assert_eq!(
+            runner.argvs(),
+            vec![
+                vec!["rbenv", "install", "3.2.2"],
+                vec!["rbenv", "global", "3.2.2"],
+            ]
         );
     }
+
+    #[test]
+    fn accepts_an_already_installed_version() {
+        let runner = ScriptedRunner::new().on(
+            "rbenv install",
+            1,
+            "",
+            "rbenv: /Users/me/.rbenv/versions/3.2.2 already exists\n",
+        );
+        assert!(rbenv(true)
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .is_ok());
+        assert_eq!(runner.argvs().len(), 2);
+    }
+
+    #[test]
+    fn fails_on_other_install_errors() {
+        let runner =
+            ScriptedRunner::new().on("rbenv install", 1, "", "BUILD FAILED (macOS 13.4)\n");
+        match rbenv(true).install(&Context::new(&runner, &SilentUi), 0) {
+            Err(Error::CommandFailed {
+                command, status, ..
+            }) => {
+                assert_eq!(command, "rbenv install 3.2.2");
+                assert_eq!(status, Some(1));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(runner.argvs().len(), 1);
+    }
+
+    #[test]
+    fn reports_a_missing_rbenv() {
+        let runner = ScriptedRunner::new().missing("rbenv");
+        assert!(matches!(
+            rbenv(false).install(&Context::new(&runner, &SilentUi), 0),
+            Err(Error::MissingCommand(_))
+        ));
+    }
+
+    #[test]
+    fn checks_installed_and_global_versions() {
+        let runner = ScriptedRunner::new()
+            .on("rbenv versions", 0, "3.1.4\n3.2.2\n", "")
+            .on("rbenv global", 0, "3.1.4\n", "");
+        assert!(rbenv(false).check(&runner));
+        assert!(!rbenv(true).check(&runner));
+    }
 }
@@ -0,0 +1,183 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+use crate::events::Event;
+
+use super::{
+    runner::{CommandRunner, CommandSpec},
+    types::{expand_home, Context, PlannedAction, Tool},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum RunCommand {
+    /// A command line run through `sh -c`
+    Shell(String),
+    /// A program and its arguments, run without a shell
+    Args(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RunOptions {
+    /// Command line or argv list to run
+    command: RunCommand,
+    /// Working directory, `~` is expanded to your home directory
+    cwd: Option<String>,
+    /// Extra environment variables for the command
+    env: Option<BTreeMap<String, String>>,
+    /// Exit codes that count as success, defaults to `[0]`
+    exit_codes: Option<Vec<i32>>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum Run {
+    Command(RunCommand),
+    Options(RunOptions),
+}
+
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    } else {
+        arg.to_string()
+    }
+}
+
+impl Run {
+    fn get_options(&self) -> RunOptions {
+        match self {
+            Run::Command(command) => RunOptions {
+                command: command.clone(),
+                cwd: None,
+                env: None,
+                exit_codes: None,
+            },
+            Run::Options(options) => options.clone(),
+        }
+    }
+
+    fn get_command(&self) -> String {
+        let options = self.get_options();
+        let mut parts = Vec::new();
+        if let Some(cwd) = &options.cwd {
+            parts.push(format!("cd {} &&", quote(cwd)));
+        }
+        for (key, value) in options.env.iter().flatten() {
+            parts.push(format!("{}={}", key, quote(value)));
+        }
+        match &options.command {
+            RunCommand::Shell(command) => parts.push(command.clone()),
+            RunCommand::Args(args) => parts.extend(args.iter().map(|a| quote(a))),
+        }
+        parts.join(" ")
+    }
+}
+
+impl Tool for Run {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        self.print_command(ctx);
+        let options = self.get_options();
+        let mut command = match &options.command {
+            RunCommand::Shell(command) => CommandSpec::new("sh").args(["-c", command]),
+            RunCommand::Args(args) => match args.split_first() {
+                Some((program, args)) => CommandSpec::new(program).args(args),
+                None => return Err(Error::Tool("The run command must not be empty".to_string())),
+            },
+        };
+        if let Some(cwd) = &options.cwd {
+            command = command.cwd(expand_home(cwd));
+        }
+        if let Some(env) = &options.env {
+            command = command.envs(env);
+        }
+        let result = ctx.run(&command)?;
+        let exit_codes = options.exit_codes.unwrap_or_else(|| vec![0]);
+        if !result.status.is_some_and(|code| exit_codes.contains(&code)) {
+            return Err(Error::CommandFailed {
+                command: self.get_command(),
+                status: result.status,
+                stderr: result.stderr,
+            });
+        }
+
+        Ok(false)
+    }
+
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self.get_command(),
+        });
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        vec![PlannedAction::Command(self.get_command())]
+    }
+
+    fn check(&self, _: &dyn CommandRunner) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn runs_strings_through_the_shell() {
+        let runner = ScriptedRunner::new();
+        serde_yaml::from_str::<Run>("echo hello && echo world")
+            .unwrap()
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        assert_eq!(
+            runner.argvs(),
+            vec![vec!["sh", "-c", "echo hello && echo world"]]
+        );
+    }
+
+    #[test]
+    fn runs_lists_without_a_shell() {
+        let runner = ScriptedRunner::new();
+        let run: Run = serde_yaml::from_str(
+            "command: [git, clone, repo]\ncwd: /tmp\nenv:\n  GIT_TERMINAL_PROMPT: '0'\n",
+        )
+        .unwrap();
+        run.install(&Context::new(&runner, &SilentUi), 0).unwrap();
+        assert_eq!(runner.argvs(), vec![vec!["git", "clone", "repo"]]);
+        let command = &runner.commands()[0];
+        assert_eq!(command.cwd, Some("/tmp".into()));
+        assert_eq!(command.env["GIT_TERMINAL_PROMPT"], "0");
+    }
+
+    #[test]
+    fn accepts_the_configured_exit_codes() {
+        let run: Run =
+            serde_yaml::from_str("command: grep -q x file\nexit_codes: [0, 1]\n").unwrap();
+        let runner = ScriptedRunner::new().on("sh -c grep", 1, "", "");
+        assert!(run.install(&Context::new(&runner, &SilentUi), 0).is_ok());
+        let runner = ScriptedRunner::new().on("sh -c grep", 2, "", "grep: file: No such file\n");
+        match run.install(&Context::new(&runner, &SilentUi), 0) {
+            Err(Error::CommandFailed { status, stderr, .. }) => {
+                assert_eq!(status, Some(2));
+                assert_eq!(stderr, "grep: file: No such file\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_an_empty_command() {
+        let runner = ScriptedRunner::new();
+        assert!(serde_yaml::from_str::<Run>("command: []")
+            .unwrap()
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .is_err());
+        assert!(runner.argvs().is_empty());
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use crate::error::{Error, Result};
+use crate::events::OutputStream;
+
+/// A program with its arguments, working directory and environment. Tools
+/// describe the commands they need with it and hand them to a
+/// `CommandRunner`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+}
+
+impl CommandSpec {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            cwd: None,
+            env: BTreeMap::new(),
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.args
+            .extend(args.into_iter().map(|a| a.as_ref().to_string()));
+        self
+    }
+
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn envs(mut self, env: &BTreeMap<String, String>) -> Self {
+        self.env.extend(env.clone());
+        self
+    }
+
+    /// Program followed by its arguments
+    pub fn argv(&self) -> Vec<&str> {
+        let mut argv = vec![self.program.as_str()];
+        argv.extend(self.args.iter().map(|a| a.as_str()));
+        argv
+    }
+
+    fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+}
+
+impl fmt::Display for CommandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.argv().join(" "))
+    }
+}
+
+/// Exit code and captured output of a finished command
+#[derive(PartialEq, Debug, Clone)]
+pub struct CommandResult {
+    pub command: String,
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+
+    pub fn into_error(self) -> Error {
+        Error::CommandFailed {
+            command: self.command,
+            status: self.status,
+            stderr: self.stderr,
+        }
+    }
+}
+
+/// Runs the commands tools need. Tools never spawn processes themselves so
+/// they can be exercised with a fake runner.
+pub trait CommandRunner {
+    /// Runs a command passing every line it writes to `output` as it comes,
+    /// so it can be shown to the user. A line the command is still writing
+    /// when it stops, e.g. a prompt, is passed on without waiting for its
+    /// end. The output is also captured so failures can be reported with it.
+    fn run(
+        &self,
+        command: &CommandSpec,
+        output: &dyn Fn(OutputStream, &str),
+    ) -> Result<CommandResult>;
+    /// Runs a command without showing its output, capturing stdout and stderr
+    fn output(&self, command: &CommandSpec) -> Result<CommandResult>;
+
+    /// Like `run`, but fails if the command exits with a non-zero status
+    fn run_checked(
+        &self,
+        command: &CommandSpec,
+        output: &dyn Fn(OutputStream, &str),
+    ) -> Result<()> {
+        let result = self.run(command, output)?;
+        if !result.success() {
+            return Err(result.into_error());
+        }
+        Ok(())
+    }
+
+    /// Returns the stdout of a command if it exited successfully
+    fn get_output(&self, command: &CommandSpec) -> Option<String> {
+        match self.output(command) {
+            Ok(result) if result.success() => Some(result.stdout),
+            _ => None,
+        }
+    }
+
+    fn has_command(&self, program: &str) -> bool {
+        self.get_output(&CommandSpec::new("which").arg(program))
+            .is_some()
+    }
+}
+
+fn spawn_error(command: &CommandSpec, e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => Error::MissingCommand(command.program.clone()),
+        _ => Error::Io(format!("Failed to run `{}`: {}", command, e)),
+    }
+}
+
+/// How long a command has to stay quiet before a line it has not finished,
+/// like a prompt waiting for an answer, is shown
+const QUIET_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Sends what `pipe` gives until it is closed
+fn read_chunks(mut pipe: impl Read, stream: OutputStream, sender: Sender<(OutputStream, Vec<u8>)>) {
+    let mut buffer = [0; 8192];
+    loop {
+        match pipe.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(read) => {
+                if sender.send((stream, buffer[..read].to_vec())).is_err() {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return,
+        }
+    }
+}
+
+/// Splits the output of a stream into lines
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Adds a chunk and returns the lines it completes, without line endings
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            lines.push(
+                line.trim_end_matches('\n')
+                    .trim_end_matches('\r')
+                    .to_string(),
+            );
+        }
+        lines
+    }
+
+    /// Takes the unfinished line, if there is one
+    fn take(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let line = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending.clear();
+        Some(line)
+    }
+}
+
+/// Runs commands on this machine
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(
+        &self,
+        command: &CommandSpec,
+        output: &dyn Fn(OutputStream, &str),
+    ) -> Result<CommandResult> {
+        let mut child = command
+            .to_command()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(command, e))?;
+        // Both pipes are read on their own thread so neither can fill up and
+        // block the child, lines are handed to `output` on this thread.
+        let (sender, receiver) = mpsc::channel();
+        let mut readers = Vec::new();
+        let pipes: [(OutputStream, Option<Box<dyn Read + Send>>); 2] = [
+            (
+                OutputStream::Stdout,
+                child
+                    .stdout
+                    .take()
+                    .map(|p| Box::new(p) as Box<dyn Read + Send>),
+            ),
+            (
+                OutputStream::Stderr,
+                child
+                    .stderr
+                    .take()
+                    .map(|p| Box::new(p) as Box<dyn Read + Send>),
+            ),
+        ];
+        for (stream, pipe) in pipes {
+            let Some(pipe) = pipe else {
+                continue;
+            };
+            let sender = sender.clone();
+            readers.push(thread::spawn(move || read_chunks(pipe, stream, sender)));
+        }
+        drop(sender);
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        let mut emit = |stream: OutputStream, line: String, complete: bool| {
+            output(stream, &line);
+            let captured = match stream {
+                OutputStream::Stdout => &mut stdout,
+                OutputStream::Stderr => &mut stderr,
+            };
+            captured.push_str(&line);
+            if complete {
+                captured.push('\n');
+            }
+        };
+        let mut buffers = [
+            (OutputStream::Stdout, LineBuffer::default()),
+            (OutputStream::Stderr, LineBuffer::default()),
+        ];
+        loop {
+            match receiver.recv_timeout(QUIET_TIMEOUT) {
+                Ok((stream, chunk)) => {
+                    let (_, buffer) = buffers.iter_mut().find(|(s, _)| *s == stream).unwrap();
+                    for line in buffer.push(&chunk) {
+                        emit(stream, line, true);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    for (stream, buffer) in &mut buffers {
+                        if let Some(line) = buffer.take() {
+                            emit(*stream, line, false);
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    for (stream, buffer) in &mut buffers {
+                        if let Some(line) = buffer.take() {
+                            emit(*stream, line, true);
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let status = child.wait().map_err(|e| spawn_error(command, e))?;
+        Ok(CommandResult {
+            command: command.to_string(),
+            status: status.code(),
+            stdout,
+            stderr,
+        })
+    }
+
+    fn output(&self, command: &CommandSpec) -> Result<CommandResult> {
+        let output = command
+            .to_command()
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| spawn_error(command, e))?;
+        Ok(CommandResult {
+            command: command.to_string(),
+            status: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+/// Records every command and reports success without running anything,
+/// e.g. to see what a project would run
+#[derive(Default)]
+pub struct RecordingRunner {
+    commands: RefCell<Vec<CommandSpec>>,
+}
+
+impl RecordingRunner {
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        self.commands.borrow().clone()
+    }
+
+    /// Recorded commands as argv lists, handy for assertions
+    pub fn argvs(&self) -> Vec<Vec<String>> {
+        self.commands
+            .borrow()
+            .iter()
+            .map(|c| c.argv().iter().map(|a| a.to_string()).collect())
+            .collect()
+    }
+
+    fn record(&self, command: &CommandSpec) -> Result<CommandResult> {
+        self.commands.borrow_mut().push(command.clone());
+        Ok(CommandResult {
+            command: command.to_string(),
+            status: Some(0),
+            stdout: String::new(),
+            stderr: String::new(),
+        })
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, command: &CommandSpec, _: &dyn Fn(OutputStream, &str)) -> Result<CommandResult> {
+        self.record(command)
+    }
+
+    fn output(&self, command: &CommandSpec) -> Result<CommandResult> {
+        self.record(command)
+    }
+}
+
+enum Response {
+    Exit {
+        status: i32,
+        stdout: String,
+        stderr: String,
+    },
+    Missing,
+}
+
+/// Answers commands from a script of canned responses and records them.
+/// Commands are matched by the start of their command line, the first
+/// matching rule wins and unmatched commands succeed with no output.
+#[derive(Default)]
+pub struct ScriptedRunner {
+    rules: Vec<(String, Response)>,
+    recorder: RecordingRunner,
+}
+
+impl ScriptedRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commands starting with `prefix` exit with `status` and print `stdout`
+    /// and `stderr`
+    pub fn on(mut self, prefix: &str, status: i32, stdout: &str, stderr: &str) -> Self {
+        self.rules.push((
+            prefix.to_string(),
+            Response::Exit {
+                status,
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+            },
+        ));
+        self
+    }
+
+    /// Commands starting with `prefix` cannot be found, as if the program
+    /// was not installed
+    pub fn missing(mut self, prefix: &str) -> Self {
+        self.rules.push((prefix.to_string(), Response::Missing));
+        self
+    }
+
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        self.recorder.commands()
+    }
+
+    pub fn argvs(&self) -> Vec<Vec<String>> {
+        self.recorder.argvs()
+    }
+
+    fn respond(&self, command: &CommandSpec) -> Result<CommandResult> {
+        let mut result = self.recorder.record(command)?;
+        let line = command.to_string();
+        match self
+            .rules
+            .iter()
+            .find(|(prefix, _)| line.starts_with(prefix))
+        {
+            Some((_, Response::Missing)) => {
+                return Err(Error::MissingCommand(command.program.clone()))
+            }
+            Some((
+                _,
+                Response::Exit {
+                    status,
+                    stdout,
+                    stderr,
+                },
+            )) => {
+                result.status = Some(*status);
+                result.stdout = stdout.clone();
+                result.stderr = stderr.clone();
+            }
+            None => {}
+        }
+        Ok(result)
+    }
+}
+
+impl CommandRunner for ScriptedRunner {
+    fn run(
+        &self,
+        command: &CommandSpec,
+        output: &dyn Fn(OutputStream, &str),
+    ) -> Result<CommandResult> {
+        let result = self.respond(command)?;
+        for line in result.stdout.lines() {
+            output(OutputStream::Stdout, line);
+        }
+        for line in result.stderr.lines() {
+            output(OutputStream::Stderr, line);
+        }
+        Ok(result)
+    }
+
+    fn output(&self, command: &CommandSpec) -> Result<CommandResult> {
+        self.respond(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_command_line() {
+        let command = CommandSpec::new("brew").args(["install", "git"]);
+        assert_eq!(command.to_string(), "brew install git");
+        assert_eq!(command.argv(), vec!["brew", "install", "git"]);
+    }
+
+    #[test]
+    fn run_checked_reports_status_and_stderr() {
+        let runner = ScriptedRunner::new().on("brew install", 1, "", "No formula\n");
+        match runner.run_checked(
+            &CommandSpec::new("brew").args(["install", "nope"]),
+            &|_, _| {},
+        ) {
+            Err(Error::CommandFailed {
+                command,
+                status,
+                stderr,
+            }) => {
+                assert_eq!(command, "brew install nope");
+                assert_eq!(status, Some(1));
+                assert_eq!(stderr, "No formula\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn get_output_ignores_failures() {
+        let runner = ScriptedRunner::new()
+            .on("brew list", 1, "git 2.41.0\n", "")
+            .on("brew --version", 0, "Homebrew 4.0\n", "");
+        assert_eq!(
+            runner.get_output(&CommandSpec::new("brew").arg("list")),
+            None
+        );
+        assert_eq!(
+            runner.get_output(&CommandSpec::new("brew").arg("--version")),
+            Some("Homebrew 4.0\n".to_string())
+        );
+    }
+
+    #[test]
+    fn system_runner_reports_missing_commands() {
+        let result = SystemRunner.run(
+            &CommandSpec::new("siu-command-that-does-not-exist"),
+            &|_, _| {},
+        );
+        assert!(matches!(result, Err(Error::MissingCommand(_))));
+    }
+
+    #[test]
+    fn system_runner_captures_output() {
+        let result = SystemRunner
+            .output(&CommandSpec::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+            .unwrap();
+        assert_eq!(result.status, Some(3));
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+    }
+
+    #[test]
+    fn system_runner_streams_both_streams() {
+        let lines = RefCell::new(Vec::new());
+        let result = SystemRunner
+            .run(
+                &CommandSpec::new("sh").args(["-c", "echo out; echo err >&2"]),
+                &|stream, line| lines.borrow_mut().push((stream, line.to_string())),
+            )
+            .unwrap();
+        let mut lines = lines.into_inner();
+        lines.sort_by_key(|(stream, _)| *stream == OutputStream::Stderr);
+        assert_eq!(
+            lines,
+            vec![
+                (OutputStream::Stdout, "out".to_string()),
+                (OutputStream::Stderr, "err".to_string()),
+            ]
+        );
+        assert_eq!(result.stdout, "out\n");
+        assert_eq!(result.stderr, "err\n");
+    }
+
+    #[test]
+    fn system_runner_shows_unfinished_lines() {
+        let lines = RefCell::new(Vec::new());
+        let result = SystemRunner
+            .run(
+                &CommandSpec::new("sh").args(["-c", "printf 'Name: '; sleep 0.5; echo done"]),
+                &|_, line| lines.borrow_mut().push(line.to_string()),
+            )
+            .unwrap();
+        assert_eq!(lines.into_inner(), vec!["Name: ", "done"]);
+        assert_eq!(result.stdout, "Name: done\n");
+    }
+
+    #[test]
+    fn system_runner_joins_lines_written_in_chunks() {
+        let lines = RefCell::new(Vec::new());
+        SystemRunner
+            .run(
+                &CommandSpec::new("sh").args(["-c", "printf 'one '; sleep 0.01; echo line"]),
+                &|_, line| lines.borrow_mut().push(line.to_string()),
+            )
+            .unwrap();
+        assert_eq!(lines.into_inner(), vec!["one line"]);
+    }
+}
@@ -0,0 +1,284 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::events::Event;
+
+use super::{
+    package_manager::PackageManager,
+    runner::{CommandRunner, CommandSpec},
+    types::{Context, PlannedAction, Tool},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SystemPackagesOptions {
+    /// Space separated list of packages to install
+    packages: String,
+    /// Repository to add before installing: a sources.list line for apt, e.g.
+    /// `deb [signed-by=/etc/apt/keyrings/docker.gpg] https://...`, or the url
+    /// of a .repo file for dnf
+    repo: Option<String>,
+    /// Url of the key the repository is signed with. apt stores it as
+    /// `/etc/apt/keyrings/<name>.gpg`
+    key: Option<String>,
+    /// Name of the apt key and sources files, defaults to the first package
+    name: Option<String>,
+    /// Refreshes the package index before installing, always done for apt
+    /// after adding a repository. pacman refreshes it in the install command,
+    /// `pacman -Sy --needed`, without upgrading the installed packages.
+    update: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(untagged)]
+pub enum SystemPackages {
+    /// Space separated list of packages to install
+    Packages(String),
+    Options(SystemPackagesOptions),
+}
+
+/// Packages of an `apt`, `dnf` or `pacman` item together with the package
+/// manager that installs them
+pub struct ManagedPackages<'a> {
+    manager: PackageManager,
+    packages: &'a SystemPackages,
+}
+
+impl SystemPackages {
+    pub fn with(&self, manager: PackageManager) -> ManagedPackages<'_> {
+        ManagedPackages {
+            manager,
+            packages: self,
+        }
+    }
+
+    fn get_options(&self) -> SystemPackagesOptions {
+        match self {
+            SystemPackages::Packages(packages) => SystemPackagesOptions {
+                packages: packages.clone(),
+                repo: None,
+                key: None,
+                name: None,
+                update: None,
+            },
+            SystemPackages::Options(options) => options.clone(),
+        }
+    }
+}
+
+impl ManagedPackages<'_> {
+    fn get_packages(&self) -> Vec<String> {
+        self.packages
+            .get_options()
+            .packages
+            .split_whitespace()
+            .map(|p| p.to_string())
+            .collect()
+    }
+
+    /// Commands adding the repository and refreshing the package index, to
+    /// be run as root
+    fn get_setup_commands(&self) -> Result<Vec<CommandSpec>> {
+        let options = self.packages.get_options();
+        let packages = self.get_packages();
+        let name = options
+            .name
+            .as_deref()
+            .or(packages.first().map(|p| p.as_str()))
+            .unwrap_or("siu");
+        let mut commands = Vec::new();
+        match self.manager {
+            PackageManager::Apt => {
+                if let Some(key) = &options.key {
+                    commands.push(CommandSpec::new("sh").args([
+                        "-c",
+                        "install -d -m 0755 /etc/apt/keyrings && curl -fsSL \"$1\" | gpg --dearmor --yes -o \"$2\"",
+                        "sh",
+                        key,
+                        &format!("/etc/apt/keyrings/{}.gpg", name),
+                    ]));
+                }
+                if let Some(repo) = &options.repo {
+                    commands.push(CommandSpec::new("sh").args([
+                        "-c",
+                        "echo \"$1\" > \"$2\"",
+                        "sh",
+                        repo,
+                        &format!("/etc/apt/sources.list.d/{}.list", name),
+                    ]));
+                }
+                if options.update.unwrap_or(false) || options.repo.is_some() {
+                    commands.push(CommandSpec::new("apt-get").arg("update"));
+                }
+            }
+            PackageManager::Dnf => {
+                if let Some(key) = &options.key {
+                    commands.push(CommandSpec::new("rpm").args(["--import", key]));
+                }
+                if let Some(repo) = &options.repo {
+                    commands.push(CommandSpec::new("dnf").args([
+                        "config-manager",
+                        "--add-repo",
+                        repo,
+                    ]));
+                }
+                if options.update.unwrap_or(false) {
+                    commands.push(CommandSpec::new("dnf").arg("makecache"));
+                }
+            }
+            PackageManager::Pacman => {
+                if options.repo.is_some() || options.key.is_some() {
+                    return Err(Error::Tool(
+                        "pacman does not support `repo` and `key`, add the repository to /etc/pacman.conf instead"
+                            .to_string(),
+                    ));
+                }
+            }
+            // `brew` items are Homebrew packages, never managed ones
+            PackageManager::Homebrew => {}
+        }
+        Ok(commands)
+    }
+
+    /// Command installing the packages, without `sudo`
+    fn get_install_command(&self) -> CommandSpec {
+        let packages = self.get_packages();
+        let update = self.packages.get_options().update.unwrap_or(false);
+        match self.manager {
+            PackageManager::Pacman if update => CommandSpec::new("pacman")
+                .args(["-Sy", "--needed", "--noconfirm"])
+                .args(&packages),
+            _ => {
+                let packages: Vec<&str> = packages.iter().map(|p| p.as_str()).collect();
+                self.manager.install_command(&packages)
+            }
+        }
+    }
+}
+
+impl Tool for ManagedPackages<'_> {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
+        for command in self.get_setup_commands()? {
+            let command = self.manager.sudo(ctx.runner, command);
+            ctx.emit(Event::CommandSpawned {
+                command: command.to_string(),
+            });
+            ctx.runner
+                .run_checked(&command, &|stream, line| ctx.output(stream, line))?;
+        }
+        self.print_command(ctx);
+        ctx.runner.run_checked(
+            &self.manager.sudo(ctx.runner, self.get_install_command()),
+            &|stream, line| ctx.output(stream, line),
+        )?;
+
+        Ok(false)
+    }
+
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self
+                .manager
+                .sudo(ctx.runner, self.get_install_command())
+                .to_string(),
+        });
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        let mut commands = match self.get_setup_commands() {
+            Ok(commands) => commands,
+            Err(e) => return vec![PlannedAction::Note(e.to_string())],
+        };
+        commands.push(self.get_install_command());
+        commands
+            .into_iter()
+            .map(|command| PlannedAction::Command(self.manager.plan_sudo(command).to_string()))
+            .collect()
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let packages = self.get_packages();
+        let packages: Vec<&str> = packages.iter().map(|p| p.as_str()).collect();
+        self.manager.is_installed(runner, &packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn adds_the_repository_before_installing() {
+        let runner = ScriptedRunner::new().on("id -u", 0, "0\n", "");
+        let packages: SystemPackages = serde_yaml::from_str("packages: docker-ce\nrepo: deb https://download.docker.com/linux/ubuntu jammy stable\nkey: https://download.docker.com/linux/ubuntu/gpg\nname: docker\n").unwrap();
+        packages
+            .with(PackageManager::Apt)
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        let argvs = runner.argvs();
+        let commands: Vec<&Vec<String>> = argvs.iter().filter(|a| a[0] != "id").collect();
+        assert_eq!(
+            commands[0][4],
+            "https://download.docker.com/linux/ubuntu/gpg"
+        );
+        assert_eq!(commands[0][5], "/etc/apt/keyrings/docker.gpg");
+        assert_eq!(commands[1][5], "/etc/apt/sources.list.d/docker.list");
+        assert_eq!(*commands[2], vec!["apt-get", "update"]);
+        assert_eq!(*commands[3], vec!["apt-get", "install", "-y", "docker-ce"]);
+    }
+
+    #[test]
+    fn rejects_pacman_repositories() {
+        let runner = ScriptedRunner::new();
+        let packages: SystemPackages =
+            serde_yaml::from_str("packages: yay\nrepo: archlinuxcn\n").unwrap();
+        assert!(packages
+            .with(PackageManager::Pacman)
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .is_err());
+        assert!(runner.argvs().is_empty());
+        let plan = packages.with(PackageManager::Pacman).plan(0);
+        assert_eq!(plan.len(), 1);
+        assert!(plan[0]
+            .to_string()
+            .starts_with("note: pacman does not support"));
+    }
+
+    #[test]
+    fn refreshes_pacman_packages_without_upgrading() {
+        let runner = ScriptedRunner::new().on("id -u", 0, "1000\n", "");
+        let packages: SystemPackages =
+            serde_yaml::from_str("packages: base-devel\nupdate: true\n").unwrap();
+        packages
+            .with(PackageManager::Pacman)
+            .install(&Context::new(&runner, &SilentUi), 0)
+            .unwrap();
+        let argvs = runner.argvs();
+        let commands: Vec<&Vec<String>> = argvs.iter().filter(|a| a[0] != "id").collect();
+        assert_eq!(
+            commands,
+            vec![&vec![
+                "sudo",
+                "pacman",
+                "-Sy",
+                "--needed",
+                "--noconfirm",
+                "base-devel"
+            ]]
+        );
+    }
+
+    #[test]
+    fn checks_installed_packages() {
+        let packages: SystemPackages = serde_yaml::from_str("git curl").unwrap();
+        let runner = ScriptedRunner::new();
+        assert!(packages.with(PackageManager::Dnf).check(&runner));
+        assert_eq!(runner.argvs(), vec![vec!["rpm", "-q", "git", "curl"]]);
+        let runner =
+            ScriptedRunner::new().on("pacman -Q", 1, "", "error: package 'curl' was not found\n");
+        assert!(!packages.with(PackageManager::Pacman).check(&runner));
+    }
+}
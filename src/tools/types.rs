@@ -1,4 +1,113 @@
+use std::fmt;
+
+use crate::error::Result;
+use crate::events::{Event, Observer, OutputStream};
+use crate::ui::Ui;
+
+use super::runner::{CommandResult, CommandRunner, CommandSpec};
+
+pub enum PlannedAction {
+    Command(String),
+    ShellConfig { path: String, line: String },
+    Pause(String),
+    Note(String),
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedAction::Command(command) => write!(f, "$ {}", command),
+            PlannedAction::ShellConfig { path, line } => {
+                write!(f, "append to {} (if missing): {}", path, line)
+            }
+            PlannedAction::Pause(message) => write!(f, "pause: {}", message),
+            PlannedAction::Note(note) => write!(f, "note: {}", note),
+        }
+    }
+}
+
+/// What tools need to do their work: a way to run commands, to talk to the
+/// user and to report events
+pub struct Context<'a> {
+    pub runner: &'a dyn CommandRunner,
+    pub ui: &'a dyn Ui,
+    pub observers: &'a [&'a dyn Observer],
+}
+
+impl<'a> Context<'a> {
+    pub fn new(runner: &'a dyn CommandRunner, ui: &'a dyn Ui) -> Self {
+        Self {
+            runner,
+            ui,
+            observers: &[],
+        }
+    }
+
+    /// Sends an event to the UI and every observer
+    pub fn emit(&self, event: Event) {
+        self.ui.on_event(&event);
+        for observer in self.observers {
+            observer.on_event(&event);
+        }
+    }
+
+    /// Reports a line a command wrote as an event, pass it to the runner
+    pub fn output(&self, stream: OutputStream, line: &str) {
+        self.emit(Event::Output {
+            stream,
+            line: line.to_string(),
+        })
+    }
+
+    /// Runs a command, reporting its output as events
+    pub fn run(&self, command: &CommandSpec) -> Result<CommandResult> {
+        self.runner
+            .run(command, &|stream, line| self.output(stream, line))
+    }
+}
+
 pub trait Tool {
-    fn install(&self, sub_step: usize) -> Result<bool, String>;
-    fn print_command(&self);
+    fn install(&self, ctx: &Context, sub_step: usize) -> Result<bool>;
+    /// Reports the command the tool is about to run
+    fn print_command(&self, ctx: &Context);
+    fn plan(&self, sub_step: usize) -> Vec<PlannedAction>;
+    /// Returns true when the tool's work is already done on this machine, so
+    /// re-running a project can skip it.
+    fn check(&self, runner: &dyn CommandRunner) -> bool;
+}
+
+/// Expands a leading `~` to the home directory
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), home::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.to_string_lossy(), rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Strips the version from a package spec like `typescript@5.1.3` or
+/// `@scope/pkg@1`.
+pub fn get_package_name(spec: &str) -> &str {
+    match spec.rfind('@') {
+        Some(index) if index > 0 => &spec[..index],
+        _ => spec,
+    }
+}
+
+/// Whether an installed `version` satisfies the version of a package spec.
+/// `5` and `5.1` match `5.1.3`, ranges and tags like `^5` or `latest` match
+/// any version.
+pub fn is_version_of(spec: &str, version: &str) -> bool {
+    let requested = &spec[get_package_name(spec).len()..];
+    let Some(requested) = requested.strip_prefix('@') else {
+        return true;
+    };
+    if !requested.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return true;
+    }
+    version == requested
+        || version
+            .strip_prefix(requested)
+            .is_some_and(|rest| rest.starts_with('.'))
 }
@@ -1,12 +1,18 @@
-use owo_colors::OwoColorize;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::process::Command;
 
-use super::types::Tool;
+use crate::error::Result;
+use crate::events::Event;
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+use super::{
+    runner::{CommandRunner, CommandSpec},
+    types::{get_package_name, is_version_of, Context, PlannedAction, Tool},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub enum Yarn {
+    /// Space separated list of packages passed to `yarn global add`
     Packages(String),
 }
 
@@ -15,25 +21,90 @@ impl Yarn {
         let Yarn::Packages(s) = self;
         s.split(' ').collect::<Vec<&str>>()
     }
+
+    fn get_command(&self) -> String {
+        let Yarn::Packages(s) = self;
+        format!("yarn global add {}", s)
+    }
 }
 
 impl Tool for Yarn {
-    fn install(&self, _: usize) -> Result<bool, String> {
+    fn install(&self, ctx: &Context, _: usize) -> Result<bool> {
         let args = [Vec::from(["global", "add"]), self.get_packages().to_owned()].concat();
-        let mut child = Command::new("yarn").args(args).spawn().unwrap();
-        match child.wait() {
-            Ok(status) => {
-                if !status.success() {
-                    return Err("Failed to install yarn global packages".to_string());
-                }
-            }
-            Err(e) => return Err(format!("Failed to run command: {}", e)),
-        }
+        self.print_command(ctx);
+        ctx.runner
+            .run_checked(&CommandSpec::new("yarn").args(args), &|stream, line| {
+                ctx.output(stream, line)
+            })?;
 
         Ok(false)
     }
-    fn print_command(&self) {
-        let Yarn::Packages(s) = self;
-        println!("\n{}\n", format!("yarn global add {}", s).italic());
+    fn print_command(&self, ctx: &Context) {
+        ctx.emit(Event::CommandSpawned {
+            command: self.get_command(),
+        });
+    }
+
+    fn plan(&self, _: usize) -> Vec<PlannedAction> {
+        vec![PlannedAction::Command(self.get_command())]
+    }
+
+    fn check(&self, runner: &dyn CommandRunner) -> bool {
+        let list = CommandSpec::new("yarn").args(["global", "list"]);
+        let Some(output) = runner.get_output(&list) else {
+            return false;
+        };
+        // Packages are listed as `info "typescript@5.1.3" has binaries:`
+        let installed: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.split('"').nth(1))
+            .collect();
+        self.get_packages().iter().all(|p| {
+            installed.iter().any(|spec| {
+                let name = get_package_name(spec);
+                name == get_package_name(p)
+                    && spec[name.len()..]
+                        .strip_prefix('@')
+                        .is_some_and(|version| is_version_of(p, version))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::runner::ScriptedRunner;
+    use crate::ui::SilentUi;
+
+    #[test]
+    fn adds_global_packages() {
+        let runner = ScriptedRunner::new();
+        let yarn = Yarn::Packages(String::from("typescript"));
+        yarn.install(&Context::new(&runner, &SilentUi), 0).unwrap();
+        assert_eq!(
+            runner.argvs(),
+            vec![vec!["yarn", "global", "add", "typescript"]]
+        );
+    }
+
+    #[test]
+    fn checks_the_global_list() {
+        let yarn = Yarn::Packages(String::from("typescript@5.1.3"));
+        let output = "info \"typescript@5.1.3\" has binaries:\n   - tsc\n";
+        let runner = ScriptedRunner::new().on("yarn global list", 0, output, "");
+        assert!(yarn.check(&runner));
+        let runner = ScriptedRunner::new().missing("yarn");
+        assert!(!yarn.check(&runner));
+    }
+
+    #[test]
+    fn checks_the_pinned_version() {
+        let yarn = Yarn::Packages(String::from("typescript@5.1.3"));
+        let output = "info \"typescript@5.0.4\" has binaries:\n   - tsc\n";
+        let runner = ScriptedRunner::new().on("yarn global list", 0, output, "");
+        assert!(!yarn.check(&runner));
+        let yarn = Yarn::Packages(String::from("typescript"));
+        assert!(yarn.check(&runner));
     }
 }
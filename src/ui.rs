@@ -0,0 +1,232 @@
+use std::io::{stdin, IsTerminal};
+
+use owo_colors::OwoColorize;
+
+use crate::diff;
+use crate::error::{Error, Result};
+use crate::events::{Event, Observer, OutputStream};
+use crate::inputs::Input;
+use crate::prompt::{self, PausePolicy};
+
+/// Everything the engine shows to or asks from the user goes through this
+/// trait, so embedders can render runs their own way. Progress is reported
+/// through the `Observer` events.
+pub trait Ui: Observer {
+    /// The project file changed since the last run
+    fn project_changed(&self, old: &str, new: &str);
+    /// A `note` from the project file
+    fn note(&self, note: &str);
+    fn info(&self, message: &str);
+    fn warning(&self, message: &str);
+
+    /// Asks a yes/no question
+    fn confirm(&self, prompt: &str) -> Result<bool>;
+    /// Waits at a `pause`. Returns false when the user wants to stop.
+    fn pause(&self) -> Result<bool>;
+    /// Lets the user pick one of `items`, `reason` explains why when no
+    /// choice can be made. Returns None if they cancelled.
+    fn select(&self, prompt: &str, items: &[String], reason: &str) -> Result<Option<usize>>;
+    /// Asks for the answer to one of the project's inputs
+    fn input(&self, input: &Input, default: Option<&str>) -> Result<String>;
+}
+
+/// Colored terminal output with dialoguer prompts, used by the CLI
+#[derive(Default)]
+pub struct TerminalUi {
+    /// Only prompt, without printing anything, e.g. when stdout is used for
+    /// machine readable events
+    pub quiet: bool,
+    /// Never prompt, fail when a decision cannot be made automatically.
+    /// Without a terminal on stdin the UI is always non-interactive.
+    pub non_interactive: bool,
+    /// Accept all confirmations, implies `non_interactive`
+    pub yes: bool,
+    /// What to do at pauses when non-interactive
+    pub on_pause: PausePolicy,
+}
+
+impl TerminalUi {
+    fn is_interactive(&self) -> bool {
+        !self.non_interactive && !self.yes && stdin().is_terminal()
+    }
+}
+
+impl Observer for TerminalUi {
+    fn on_event(&self, event: &Event) {
+        if self.quiet {
+            return;
+        }
+        match event {
+            Event::ProjectStarted {
+                project,
+                description,
+                ..
+            } => {
+                let title = format!("Will setup \"{}\"", project);
+                println!("\n\n{}", title.green().bold());
+                println!("{}\n\n", description.cyan());
+            }
+            Event::ProjectResumed { .. } => {
+                println!("{}", "Picking up where you left off".green().bold())
+            }
+            Event::StepStarted { description, .. } => {
+                println!("\n{}", description.underline().bold())
+            }
+            Event::StepSkipped {
+                description,
+                reason,
+                ..
+            } => println!(
+                "\n{} {}",
+                description.underline().dimmed(),
+                format!("(skipped, {})", reason).italic()
+            ),
+            Event::ToolSkipped { name, reason, .. } => println!(
+                "\n{}",
+                format!("{} {}, skipping", name, reason).green().italic()
+            ),
+            Event::CommandSpawned { command } => println!("\n{}\n", command.italic()),
+            Event::Output {
+                stream: OutputStream::Stdout,
+                line,
+            } => println!("{}", line),
+            Event::Output {
+                stream: OutputStream::Stderr,
+                line,
+            } => eprintln!("{}", line),
+            _ => {}
+        }
+    }
+}
+
+impl Ui for TerminalUi {
+    fn project_changed(&self, old: &str, new: &str) {
+        if self.quiet {
+            return;
+        }
+        println!(
+            "{}\n",
+            "The project file changed since your last run:"
+                .yellow()
+                .bold()
+        );
+        diff::print_diff(old, new);
+        println!();
+    }
+
+    fn note(&self, note: &str) {
+        if !self.quiet {
+            println!("\n\n{}\n", note.bold());
+        }
+    }
+
+    fn info(&self, message: &str) {
+        if !self.quiet {
+            println!("\n{}", message);
+        }
+    }
+
+    fn warning(&self, message: &str) {
+        if !self.quiet {
+            println!("\n{}", message.yellow().bold());
+        }
+    }
+
+    /// Accepted with `yes` and fails otherwise when non-interactive
+    fn confirm(&self, prompt: &str) -> Result<bool> {
+        if self.is_interactive() {
+            return prompt::confirm(prompt);
+        }
+        if self.yes {
+            return Ok(true);
+        }
+        Err(Error::Input(format!(
+            "Cannot ask \"{}\" in non-interactive mode, pass --yes to accept",
+            prompt
+        )))
+    }
+
+    fn pause(&self) -> Result<bool> {
+        if self.is_interactive() {
+            return prompt::pause();
+        }
+        match self.on_pause {
+            PausePolicy::Continue => Ok(true),
+            PausePolicy::Stop => Ok(false),
+            PausePolicy::Fail => Err(Error::Input(
+                "Reached a pause in non-interactive mode, use --on-pause to continue or stop"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Fails when non-interactive since there is no safe default
+    fn select(&self, prompt: &str, items: &[String], reason: &str) -> Result<Option<usize>> {
+        if self.is_interactive() {
+            return prompt::select(prompt, items);
+        }
+        Err(Error::Input(format!(
+            "{}, cannot decide in non-interactive mode",
+            reason
+        )))
+    }
+
+    /// Uses the default when non-interactive, there must be one
+    fn input(&self, input: &Input, default: Option<&str>) -> Result<String> {
+        if self.is_interactive() {
+            return prompt::input(input, default);
+        }
+        default.map(|d| d.to_string()).ok_or_else(|| {
+            Error::Input(format!(
+                "Cannot ask \"{}\" in non-interactive mode, pass --set {}=...",
+                input.get_prompt(),
+                input.name
+            ))
+        })
+    }
+}
+
+/// Prints nothing and never prompts. Confirmations and selections fail like
+/// in non-interactive mode and pauses continue.
+pub struct SilentUi;
+
+impl Observer for SilentUi {
+    fn on_event(&self, _: &Event) {}
+}
+
+impl Ui for SilentUi {
+    fn project_changed(&self, _: &str, _: &str) {}
+
+    fn note(&self, _: &str) {}
+
+    fn info(&self, _: &str) {}
+
+    fn warning(&self, _: &str) {}
+
+    fn confirm(&self, prompt: &str) -> Result<bool> {
+        Err(Error::Input(format!(
+            "Cannot ask \"{}\" without a user",
+            prompt
+        )))
+    }
+
+    fn pause(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn select(&self, _: &str, _: &[String], reason: &str) -> Result<Option<usize>> {
+        Err(Error::Input(format!(
+            "{}, cannot decide without a user",
+            reason
+        )))
+    }
+
+    fn input(&self, input: &Input, default: Option<&str>) -> Result<String> {
+        default.map(|d| d.to_string()).ok_or_else(|| {
+            Error::Input(format!(
+                "Cannot ask \"{}\" without a user",
+                input.get_prompt()
+            ))
+        })
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::inputs::InputType;
+use crate::projects::{YamlConfiguration, TOOL_NAMES};
+
+pub struct ValidationError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Index and description of the step the error was found in
+    pub step: Option<(usize, String)>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some((index, description)) = &self.step {
+            write!(f, "\n  in step {} \"{}\"", index + 1, description)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn unknown_tool_message(name: &str) -> String {
+    let suggestion = TOOL_NAMES
+        .iter()
+        .map(|tool| (tool, strsim::damerau_levenshtein(name, tool)))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance);
+    match suggestion {
+        Some((tool, _)) => format!(
+            "unknown tool `{}`, did you mean `{}`? Expected one of: {}",
+            name,
+            tool,
+            TOOL_NAMES.join(", ")
+        ),
+        None => format!(
+            "unknown tool `{}`, expected one of: {}",
+            name,
+            TOOL_NAMES.join(", ")
+        ),
+    }
+}
+
+/// Extracts the step index from a serde_yaml error path like
+/// `steps[2].run[0].rbenv`.
+fn get_step_index(message: &str) -> Option<usize> {
+    let rest = message.strip_prefix("steps[")?;
+    let end = rest.find(']')?;
+    rest[..end].parse().ok()
+}
+
+fn get_step_description(source: &str, index: usize) -> Option<String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(source).ok()?;
+    value
+        .get("steps")?
+        .get(index)?
+        .get("description")?
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+/// Checks what serde can't: the project has steps, ids and input names are
+/// unique and inputs are complete.
+fn check(options: &YamlConfiguration) -> Result<(), (Option<usize>, String)> {
+    if options.steps.is_empty() {
+        return Err((None, "a project needs at least one step".to_string()));
+    }
+    let mut input_names = HashSet::new();
+    for input in &options.inputs {
+        if !input_names.insert(&input.name) || options.vars.contains_key(&input.name) {
+            return Err((
+                None,
+                format!("duplicate input or variable `{}`", input.name),
+            ));
+        }
+        if input.kind == InputType::Select && input.options.iter().flatten().next().is_none() {
+            return Err((None, format!("select input `{}` needs options", input.name)));
+        }
+        if let Some(default) = &input.default {
+            input.validate(default).map_err(|e| {
+                (
+                    None,
+                    format!("invalid default of input `{}`: {}", input.name, e),
+                )
+            })?;
+        }
+    }
+    let mut step_ids = HashSet::new();
+    for (index, step) in options.steps.iter().enumerate() {
+        if let Some(id) = &step.id {
+            if !step_ids.insert(id) {
+                return Err((Some(index), format!("duplicate step id `{}`", id)));
+            }
+        }
+        let mut tool_ids = HashSet::new();
+        for tool in &step.run {
+            if let Some(id) = &tool.id {
+                if !tool_ids.insert(id) {
+                    return Err((Some(index), format!("duplicate tool id `{}`", id)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses a project file without checking it or filling in its variables,
+/// e.g. before merging the files it includes
+pub fn read(source: &str, file: &str) -> Result<YamlConfiguration, ValidationError> {
+    serde_yaml::from_str::<YamlConfiguration>(source).map_err(|e| {
+        let location = e.location();
+        let mut message = e.to_string();
+        if let Some(l) = &location {
+            let suffix = format!(" at line {} column {}", l.line(), l.column());
+            if let Some(stripped) = message.strip_suffix(&suffix) {
+                message = stripped.to_string();
+            }
+        }
+        let step = get_step_index(&message).map(|index| {
+            (
+                index,
+                get_step_description(source, index).unwrap_or_default(),
+            )
+        });
+        ValidationError {
+            file: file.to_string(),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column()),
+            step,
+            message,
+        }
+    })
+}
+
+/// Checks a parsed project. Errors in included steps name the file the step
+/// came from. Variables are filled in later, once the input answers are
+/// known.
+pub fn validate(
+    options: YamlConfiguration,
+    file: &str,
+) -> Result<YamlConfiguration, ValidationError> {
+    let Err((step, message)) = check(&options) else {
+        return Ok(options);
+    };
+    let step = step.map(|index| (index, &options.steps[index]));
+    Err(ValidationError {
+        file: step
+            .and_then(|(_, s)| s.file.clone())
+            .unwrap_or_else(|| file.to_string()),
+        line: None,
+        column: None,
+        step: step.map(|(index, s)| (index, s.description.clone())),
+        message,
+    })
+}
+
+/// Parses and checks a project file
+pub fn parse(source: &str, file: &str) -> Result<YamlConfiguration, ValidationError> {
+    validate(read(source, file)?, file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match parse(source, "project.yaml") {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("the project is valid"),
+        }
+    }
+
+    #[test]
+    fn suggests_the_closest_tool() {
+        let message = error(
+            "name: p\ndescription: d\nsteps:\n  - description: first\n    run:\n      - note: a\n  - description: second\n    run:\n      - rbnev: { ruby_version: 3.2.2 }\n",
+        );
+        assert!(message.starts_with(
+            "project.yaml:9:9: steps[1].run[0]: unknown tool `rbnev`, did you mean `rbenv`? Expected one of: apt,"
+        ));
+        assert!(message.ends_with("\n  in step 2 \"second\""));
+        assert!(!unknown_tool_message("docker").contains("did you mean"));
+    }
+
+    #[test]
+    fn reports_wrong_types() {
+        assert_eq!(
+            error("name: p\ndescription: d\nsteps:\n  - description: first\n    run:\n      - node: { versions: 20 }\n"),
+            "project.yaml:6:27: steps[0].run[0].node.versions: invalid type: integer `20`, expected a sequence\n  in step 1 \"first\""
+        );
+    }
+
+    #[test]
+    fn rejects_java11_false() {
+        assert!(error("name: p\ndescription: d\nsteps:\n  - description: first\n    run:\n      - java11: false\n")
+            .contains("`java11: false` installs nothing, remove the item instead"));
+    }
+
+    #[test]
+    fn ignores_only_x_keys() {
+        let options = parse(
+            "name: p\ndescription: d\nx-note: &note { note: hi }\nsteps:\n  - description: first\n    run:\n      - *note\n",
+            "project.yaml",
+        );
+        assert!(options.is_ok());
+        assert!(error("name: p\ndescription: d\nnote: hi\nsteps:\n  - description: first\n    run:\n      - note: hi\n")
+            .contains("unknown field `note`, expected one of `name`, `description`, `include`, `vars`, `inputs`, `steps` or a key starting with `x-`"));
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        assert_eq!(
+            error("name: p\ndescription: d\nsteps:\n  - description: first\n    id: setup\n    run:\n      - note: a\n  - description: second\n    id: setup\n    run:\n      - note: b\n"),
+            "project.yaml: duplicate step id `setup`\n  in step 2 \"second\""
+        );
+        assert_eq!(
+            error("name: p\ndescription: d\nsteps:\n  - description: first\n    run:\n      - { id: a, note: a }\n      - { id: a, note: b }\n"),
+            "project.yaml: duplicate tool id `a`\n  in step 1 \"first\""
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::env;
+
+use serde_yaml::Value;
+
+use crate::projects::YamlConfiguration;
+
+/// Parses a `name=value` assignment
+pub fn parse_assignment(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected `name=value`, got `{}`", s)),
+    }
+}
+
+/// Returns the value of a variable set in `overrides`, e.g. with
+/// `--set name=value`, or with `SIU_VAR_<name>`
+pub fn get_override(name: &str, overrides: &BTreeMap<String, String>) -> Option<String> {
+    overrides
+        .get(name)
+        .cloned()
+        .or_else(|| env::var(format!("SIU_VAR_{}", name)).ok())
+}
+
+/// The project's `vars` and input answers, overridden by `SIU_VAR_<name>`
+/// environment variables and then by `overrides`.
+fn get_vars(
+    defaults: &BTreeMap<String, String>,
+    answers: &BTreeMap<String, String>,
+    overrides: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let mut vars = defaults.clone();
+    vars.extend(answers.clone());
+    for (key, value) in env::vars() {
+        if let Some(name) = key.strip_prefix("SIU_VAR_") {
+            vars.insert(name.to_string(), value);
+        }
+    }
+    vars.extend(overrides.clone());
+    vars
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replaces every `{{ name }}` in `text` with the value of the variable.
+/// Anything else between braces, like docker's `{{.Id}}`, is left as written.
+pub fn interpolate(text: &str, vars: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        if !is_identifier(name) {
+            result.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        result.push_str(&rest[..start]);
+        match vars.get(name) {
+            Some(value) => result.push_str(value),
+            None => {
+                return Err(format!(
+                    "undefined variable `{}`, set it in `vars`, with `--set {}=...` or SIU_VAR_{}",
+                    name, name, name
+                ))
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn interpolate_value(value: &mut Value, vars: &BTreeMap<String, String>) -> Result<(), String> {
+    match value {
+        Value::String(s) => *s = interpolate(s, vars)?,
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_value(item, vars)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, item) in mapping.iter_mut() {
+                interpolate_value(item, vars)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, vars)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// Interpolates the variables and the `answers` to the project's inputs into
+/// every string of the project besides `name` and `vars` itself, `overrides`
+/// taking precedence. Inputs that were not answered yet are left as they
+/// are. Errors include the index of the step they were found in.
+pub fn apply(
+    options: YamlConfiguration,
+    answers: &BTreeMap<String, String>,
+    overrides: &BTreeMap<String, String>,
+) -> Result<YamlConfiguration, (Option<usize>, String)> {
+    let mut vars = get_vars(&options.vars, answers, overrides);
+    for input in &options.inputs {
+        if !vars.contains_key(&input.name) {
+            vars.insert(input.name.clone(), format!("{{{{ {} }}}}", input.name));
+        }
+    }
+    let mut value = serde_yaml::to_value(&options).map_err(|e| (None, e.to_string()))?;
+    let Value::Mapping(mapping) = &mut value else {
+        return Ok(options);
+    };
+    for (key, item) in mapping.iter_mut() {
+        match key.as_str() {
+            // The name identifies the saved progress
+            Some("vars") | Some("name") => {}
+            Some("steps") => {
+                for (index, step) in item.as_sequence_mut().into_iter().flatten().enumerate() {
+                    interpolate_value(step, &vars).map_err(|e| (Some(index), e))?;
+                }
+            }
+            _ => interpolate_value(item, &vars).map_err(|e| (None, e))?,
+        }
+    }
+    let mut result: YamlConfiguration =
+        serde_yaml::from_value(value).map_err(|e| (None, e.to_string()))?;
+    // Not serialized, the steps keep their order
+    for (step, original) in result.steps.iter_mut().zip(options.steps) {
+        step.file = original.file;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_variables() {
+        let vars = BTreeMap::from([("ruby".to_string(), "3.2.2".to_string())]);
+        assert_eq!(
+            interpolate("rbenv install {{ ruby }} && echo {{ruby}}", &vars).unwrap(),
+            "rbenv install 3.2.2 && echo 3.2.2"
+        );
+        assert!(interpolate("{{ node }}", &vars).is_err());
+    }
+
+    #[test]
+    fn leaves_other_braces_as_written() {
+        let vars = BTreeMap::from([("ruby".to_string(), "3.2.2".to_string())]);
+        let docker = r#"docker inspect -f "{{.Id}}" foo"#;
+        assert_eq!(interpolate(docker, &vars).unwrap(), docker);
+        assert_eq!(
+            interpolate("{{ json .Config }} {{ruby}}", &vars).unwrap(),
+            "{{ json .Config }} 3.2.2"
+        );
+        assert_eq!(interpolate("{{ ruby", &vars).unwrap(), "{{ ruby");
+        assert_eq!(interpolate("{{{{ruby}}", &vars).unwrap(), "{{3.2.2");
+    }
+
+    #[test]
+    fn parses_assignments() {
+        assert_eq!(
+            parse_assignment("ruby_version=3.2.2").unwrap(),
+            ("ruby_version".to_string(), "3.2.2".to_string())
+        );
+        assert_eq!(
+            parse_assignment("url=a=b").unwrap(),
+            ("url".to_string(), "a=b".to_string())
+        );
+        assert!(parse_assignment("ruby_version").is_err());
+    }
+
+    #[test]
+    fn applies_vars_to_steps() {
+        let options: YamlConfiguration = serde_yaml::from_str(
+            "name: p\ndescription: '{{ who }}'\nvars:\n  who: me\n  ruby: 3.2.2\nsteps:\n  - description: Ruby {{ ruby }}\n    run:\n      - rbenv: { install: true, ruby_version: '{{ ruby }}' }\n",
+        )
+        .unwrap();
+        let options = apply(options, &BTreeMap::new(), &BTreeMap::new()).unwrap();
+        assert_eq!(options.description, "me");
+        assert_eq!(options.steps[0].description, "Ruby 3.2.2");
+        assert!(serde_yaml::to_string(&options.steps[0].run[0])
+            .unwrap()
+            .contains("ruby_version: 3.2.2"));
+    }
+
+    #[test]
+    fn keeps_unanswered_inputs() {
+        let options: YamlConfiguration = serde_yaml::from_str(
+            "name: p\ndescription: d\ninputs:\n  - name: email\nsteps:\n  - description: Git {{ email }}\n    run:\n      - note: hi\n",
+        )
+        .unwrap();
+        let options = apply(options, &BTreeMap::new(), &BTreeMap::new()).unwrap();
+        assert_eq!(options.steps[0].description, "Git {{ email }}");
+        let answers = BTreeMap::from([("email".to_string(), "dev@example.com".to_string())]);
+        let options = apply(options, &answers, &BTreeMap::new()).unwrap();
+        assert_eq!(options.steps[0].description, "Git dev@example.com");
+    }
+}